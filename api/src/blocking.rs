@@ -0,0 +1,112 @@
+//! Synchronous versions of the main functions of this crate, for programs that are not
+//! async. Requires the `blocking` feature.
+//!
+//! Each call starts a small Tokio runtime and waits for the request to complete, so it
+//! is a bit slower than its async counterpart, and several calls are never run
+//! concurrently. These functions panic if called from an async context, such as a
+//! function run by `#[tokio::main]`: use the async functions there instead.
+//!
+//! ## Example
+//!
+//! ```
+//! use hnapi::{blocking, StoryList};
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let stories = blocking::stories_list(StoryList::News, 1, &None)?;
+//!     assert_eq!(stories.len(), 30);
+//!     let details = blocking::story_details(stories[&1].id)?;
+//!     assert!(details.is_some());
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    Auth, Comment, CommentFetchBudget, HnError, Story, StoryList, StoryPage, StoryWithDetails, User,
+};
+use std::{collections::HashMap, future::Future, rc::Rc};
+
+/// Run a future to completion on a new single-threaded runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("Unable to start the Tokio runtime")
+        .block_on(future)
+}
+
+/// Blocking version of [`stories_list`](crate::stories_list).
+pub fn stories_list(
+    list: StoryList,
+    page: usize,
+    token: &Option<String>,
+) -> Result<HashMap<usize, Story>, HnError> {
+    block_on(crate::stories_list(list, page, token))
+}
+
+/// Blocking version of [`stories_page`](crate::stories_page).
+pub fn stories_page(
+    list: StoryList,
+    page: usize,
+    token: &Option<String>,
+) -> Result<StoryPage, HnError> {
+    block_on(crate::stories_page(list, page, token))
+}
+
+/// Blocking version of [`next_stories_page`](crate::next_stories_page).
+pub fn next_stories_page(
+    previous: &StoryPage,
+    token: &Option<String>,
+) -> Result<Option<StoryPage>, HnError> {
+    block_on(crate::next_stories_page(previous, token))
+}
+
+/// Blocking version of [`story_details`](crate::story_details).
+pub fn story_details(id: u32) -> Result<Option<StoryWithDetails>, HnError> {
+    block_on(crate::story_details(id))
+}
+
+/// Blocking version of [`story_details_with_budget`](crate::story_details_with_budget).
+pub fn story_details_with_budget(
+    id: u32,
+    budget: &CommentFetchBudget,
+) -> Result<Option<StoryWithDetails>, HnError> {
+    block_on(crate::story_details_with_budget(id, budget))
+}
+
+/// Blocking version of [`comment_thread`](crate::comment_thread).
+pub fn comment_thread(id: u32) -> Result<Option<Rc<Comment>>, HnError> {
+    block_on(crate::comment_thread(id))
+}
+
+/// Blocking version of [`user_details`](crate::user_details).
+pub fn user_details(id: &str) -> Result<Option<User>, HnError> {
+    block_on(crate::user_details(id))
+}
+
+/// Blocking version of [`user_submissions`](crate::user_submissions).
+pub fn user_submissions(
+    id: &str,
+    token: &Option<String>,
+) -> Result<HashMap<usize, Story>, HnError> {
+    block_on(crate::user_submissions(id, token))
+}
+
+/// Blocking version of [`user_comments`](crate::user_comments).
+pub fn user_comments(id: &str) -> Result<Vec<Rc<Comment>>, HnError> {
+    block_on(crate::user_comments(id))
+}
+
+/// Blocking version of [`login`](crate::login).
+pub fn login(username: &str, password: &str) -> Result<Option<Auth>, HnError> {
+    block_on(crate::login(username, password))
+}
+
+/// Blocking version of [`upvote_story`](crate::upvote_story).
+pub fn upvote_story(id: u32, upvote_auth: &str, token: &str) -> Result<bool, HnError> {
+    block_on(crate::upvote_story(id, upvote_auth, token))
+}
+
+/// Blocking version of [`current_user`](crate::current_user).
+pub fn current_user(token: &str) -> Result<Option<String>, HnError> {
+    block_on(crate::current_user(token))
+}
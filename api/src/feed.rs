@@ -0,0 +1,179 @@
+//! Generate [Atom](https://datatracker.ietf.org/doc/html/rfc4287) feeds from stories.
+//!
+//! ## Example
+//!
+//! ```
+//! use hnapi::{feed, stories_list, StoryList};
+//!
+//! #[tokio::main]
+//! async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let stories = stories_list(StoryList::News, 1, &None).await?;
+//!     let mut ranks: Vec<&usize> = stories.keys().collect();
+//!     ranks.sort_unstable();
+//!     let xml = feed::atom("HackerNews", ranks.iter().map(|rank| &stories[rank]));
+//!     assert!(xml.contains("<entry>"));
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{Story, BASE_URL};
+use chrono::Utc;
+
+/// Generate an Atom feed containing the given stories, in the same order. Each entry
+/// links to the story article, and to its comments on HackerNews.
+pub fn atom<'a>(title: &str, stories: impl IntoIterator<Item = &'a Story>) -> String {
+    let stories: Vec<&Story> = stories.into_iter().collect();
+    let updated = stories
+        .iter()
+        .map(|story| story.date)
+        .max()
+        .unwrap_or_else(Utc::now);
+    let entries: String = stories.iter().map(|story| atom_entry(story)).collect();
+    format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?>"#,
+            "\n",
+            r#"<feed xmlns="http://www.w3.org/2005/Atom">"#,
+            "<id>{}/</id>",
+            "<title>{}</title>",
+            r#"<link rel="alternate" href="{}/"/>"#,
+            "<updated>{}</updated>",
+            "<author><name>HackerNews</name></author>",
+            "{}",
+            "</feed>\n"
+        ),
+        BASE_URL,
+        escape(title),
+        BASE_URL,
+        updated.to_rfc3339(),
+        entries
+    )
+}
+
+fn atom_entry(story: &Story) -> String {
+    let comments_url = story.comments_url();
+    format!(
+        concat!(
+            "<entry>",
+            "<id>{}</id>",
+            // Titles scraped from HackerNews are HTML, e.g. with `&amp;` entities.
+            r#"<title type="html">{}</title>"#,
+            r#"<link rel="alternate" href="{}"/>"#,
+            r#"<link rel="replies" type="text/html" href="{}"/>"#,
+            "{}",
+            "<published>{}</published>",
+            "<updated>{}</updated>",
+            "</entry>"
+        ),
+        escape(&comments_url),
+        escape(&story.title),
+        escape(story.url.as_str()),
+        escape(&comments_url),
+        story
+            .user
+            .as_deref()
+            .map(|user| format!("<author><name>{}</name></author>", escape(user)))
+            .unwrap_or_default(),
+        story.date.to_rfc3339(),
+        story.date.to_rfc3339(),
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StoryKind;
+    use chrono::DateTime;
+    use std::str::FromStr;
+    use url::Url;
+
+    fn story(id: u32, title: &str, url: &str, user: Option<&str>) -> Story {
+        Story {
+            id,
+            title: title.to_string(),
+            url: Url::from_str(url).unwrap(),
+            url_displayed: None,
+            upvote_auth: None,
+            user: user.map(ToString::to_string),
+            score: Some(42),
+            date: DateTime::from_str("2021-07-19T14:33:05.000Z").unwrap(),
+            date_displayed: "3 hours ago".to_string(),
+            comment_count: Some(12),
+            kind: StoryKind::Regular,
+        }
+    }
+
+    #[test]
+    fn atom_feed_can_be_parsed() {
+        let stories = vec![
+            story(
+                27883047,
+                "Julia Computing raises $24M Series A &amp; more",
+                "https://www.hpcwire.com/?a=1&b=2",
+                Some("dklend122"),
+            ),
+            story(
+                29203502,
+                "Lifeee <3",
+                "https://news.ycombinator.com/item?id=29203502",
+                None,
+            ),
+        ];
+        let xml = atom("Top \"stories\"", &stories);
+
+        let document = roxmltree::Document::parse(&xml).unwrap();
+        let feed = document.root_element();
+        assert_eq!(feed.tag_name().name(), "feed");
+        let child_text = |node: roxmltree::Node, name: &str| {
+            node.children()
+                .find(|child| child.tag_name().name() == name)
+                .and_then(|child| child.text())
+                .map(ToString::to_string)
+        };
+        assert_eq!(
+            child_text(feed, "title"),
+            Some("Top \"stories\"".to_string())
+        );
+
+        let entries: Vec<_> = feed
+            .children()
+            .filter(|child| child.tag_name().name() == "entry")
+            .collect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            child_text(entries[0], "title"),
+            Some("Julia Computing raises $24M Series A &amp; more".to_string())
+        );
+        let links: Vec<_> = entries[0]
+            .children()
+            .filter(|child| child.tag_name().name() == "link")
+            .map(|link| link.attribute("href").unwrap())
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                "https://www.hpcwire.com/?a=1&b=2",
+                "https://news.ycombinator.com/item?id=27883047"
+            ]
+        );
+        assert_eq!(
+            child_text(entries[0], "published"),
+            Some("2021-07-19T14:33:05+00:00".to_string())
+        );
+        assert_eq!(
+            child_text(entries[1], "title"),
+            Some("Lifeee <3".to_string())
+        );
+        assert!(!entries[1]
+            .children()
+            .any(|child| child.tag_name().name() == "author"));
+    }
+}
@@ -2,10 +2,34 @@
 //!
 //! For now, it supports the following operations:
 //!   - list stories using [`stories_list`]
-//!   - get details and comments for a story using [`story_details`]
-//!   - get details about a user using [`user_details`]
+//!   - list stories page by page using [`stories_page`] and [`next_stories_page`]
+//!   - list the stories that appeared since a previous call using [`stories_since`]
+//!   - get details and comments for a story using [`story_details`], or several stories
+//!     using [`stories_details`]
+//!   - get the score and comment count of a story using [`story_stats`]
+//!   - estimate the time needed to read a story using [`story_reading_time`]
+//!   - get a comment and its replies using [`comment_thread`], or all of them using
+//!     [`expand_comment`]
+//!   - get details about a user using [`user_details`], or several users using [`users`]
+//!   - get the stories and comments posted by a user using [`user_submissions`] and
+//!     [`user_comments`], or page by page using [`user_submissions_page`] and
+//!     [`user_comments_page`]
+//!   - find the previous discussions about a URL using [`past_discussions`]
 //!   - login and get an auth token using [`login`]
 //!   - upvote a story using [`upvote_story`]
+//!   - get the user an auth token belongs to using [`current_user`]
+//!   - estimate how often a user posts using [`user_activity`]
+//!   - count the new replies to your comments using [`unread_replies`] or
+//!     [`replies_since`]
+//!   - get your own profile settings using [`my_profile`], and update your about text
+//!     using [`update_about`]
+//!   - edit and delete your comments using [`edit_comment`] and [`delete_comment`]
+//!   - check that the markup of HackerNews is still supported using [`check_markup`]
+//!   - display a date the way HackerNews does using [`humanize`]
+//!   - generate an Atom feed from stories using [`feed::atom`]
+//!   - read pages of HackerNews fetched by other means using the [`parse`] module,
+//!     which is the only one available without the default `fetch` feature (e.g. to
+//!     build for WebAssembly)
 //!
 //! Refer to their respective documentations to see usage examples.
 //!
@@ -14,29 +38,56 @@
 //! not provide a convenient way to get all the comments for a given story, and only allows
 //! read operations.
 
-use chrono::{DateTime, NaiveDate, Utc};
+#[cfg(feature = "fetch")]
+use chrono::TimeZone;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::{debug, warn};
 use regex::Regex;
+#[cfg(feature = "fetch")]
 use reqwest::header::COOKIE;
 use scraper::{ElementRef, Html, Selector};
 use serde::{Deserialize, Serialize};
 use std::{
     cell::RefCell,
+    cmp::Reverse,
     collections::HashMap,
     error::Error,
+    fmt,
     rc::{Rc, Weak},
     str::FromStr,
+    time::Instant,
 };
-use url::Url;
+#[cfg(feature = "fetch")]
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+};
+#[cfg(feature = "fetch")]
+use tokio::sync::Semaphore;
+use url::{Host, Url};
 
 extern crate chrono;
+extern crate log;
+#[cfg(feature = "fetch")]
 extern crate reqwest;
 extern crate scraper;
 extern crate serde;
 extern crate url;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod feed;
+pub mod parse;
+
 const BASE_URL: &str = "https://news.ycombinator.com";
+#[cfg(feature = "fetch")]
+const SEARCH_URL: &str = "https://hn.algolia.com/api/v1/search";
+#[cfg(feature = "firebase")]
+const FIREBASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
+/// Reading speed used to estimate reading times.
+const WORDS_PER_MINUTE: usize = 200;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 /// Information about a story.
 pub struct Story {
     /// ID of the story.
@@ -61,6 +112,95 @@ pub struct Story {
     pub date_displayed: String,
     /// Number of comments posted on the story.
     pub comment_count: Option<u32>,
+    /// Whether it is a regular story or a job offer.
+    #[serde(default)]
+    pub kind: StoryKind,
+}
+
+/// Kind of a story, as they are not all displayed the same way.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum StoryKind {
+    /// Story posted by a user, with a score and comments (including “Ask HN” and
+    /// “Show HN” stories).
+    #[default]
+    Regular,
+    /// Job offer, e.g. from a YC company (“… is hiring”). It has no user, no score and
+    /// can’t be commented or upvoted.
+    Job,
+}
+
+impl Story {
+    /// Whether the story links to a given domain or one of its subdomains, e.g.
+    /// `github.com` matches stories from `github.com`, `www.github.com` and
+    /// `gist.github.com`, but not from `notgithub.com`. Case is ignored.
+    pub fn matches_domain(&self, domain: &str) -> bool {
+        let domain = domain.trim().trim_start_matches("www.").to_lowercase();
+        self.url.host_str().is_some_and(|host| {
+            let host = host.to_lowercase();
+            host == domain || host.ends_with(&format!(".{}", domain))
+        })
+    }
+
+    /// Domain the story links to without its subdomains, e.g. `github.com` for
+    /// `https://gist.github.com/someone`, unlike `url_displayed` which is whatever
+    /// HackerNews displays. IP addresses are returned as they are. Will be `None` if the
+    /// URL has no host.
+    ///
+    /// The domain is found using the [public suffix list](https://publicsuffix.org/), so
+    /// that e.g. `news.bbc.co.uk` gives `bbc.co.uk` and `foo.github.io` is kept as it is.
+    /// Hosts without a known suffix (e.g. `localhost`) are returned as they are.
+    pub fn display_domain(&self) -> Option<String> {
+        match self.url.host()? {
+            Host::Domain(domain) => Some(psl::domain_str(domain).unwrap_or(domain).to_string()),
+            host => Some(host.to_string()),
+        }
+    }
+
+    /// Whether the story is a text post (e.g. most “Ask HN” stories) rather than a link.
+    /// The `url` of text posts is their own discussion page on HackerNews; a story
+    /// linking to another item of HackerNews is not a text post.
+    pub fn is_text_post(&self) -> bool {
+        self.url.domain() == Some("news.ycombinator.com")
+            && self.url.path() == "/item"
+            && self
+                .url
+                .query_pairs()
+                .any(|(key, value)| key == "id" && value == self.id.to_string())
+    }
+
+    /// URL of the story’s page on HackerNews, where it is discussed. For text stories,
+    /// it is the same as `url`.
+    pub fn comments_url(&self) -> String {
+        format!("{}/item?id={}", BASE_URL, self.id)
+    }
+
+    /// Story URL without its tracking parameters, such as `utm_source` or `fbclid`. The
+    /// other parameters are kept in the same order. `url` is left unchanged.
+    pub fn clean_url(&self) -> Url {
+        let mut url = self.url.clone();
+        let params: Vec<(String, String)> = self
+            .url
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_param(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if params.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(params);
+        }
+        url
+    }
+}
+
+/// Query parameters only used to track where visitors come from.
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "msclkid", "yclid", "igshid", "mc_cid", "mc_eid", "_hsenc",
+    "_hsmi", "ref_src",
+];
+
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name)
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -76,6 +216,55 @@ pub struct User {
     pub about: String,
 }
 
+impl User {
+    /// The about text without its HTML markup, for consumers that can’t render it.
+    /// Paragraphs are separated by a blank line, and links are replaced with their
+    /// full URL (HackerNews truncates long ones in their text).
+    pub fn about_text(&self) -> String {
+        let fragment = Html::parse_fragment(&self.about);
+        let mut paragraphs = vec![String::new()];
+        for child in fragment.root_element().children() {
+            match ElementRef::wrap(child) {
+                Some(el) if el.value().name() == "p" => paragraphs.push(plain_text(&el)),
+                Some(el) => paragraphs.last_mut().unwrap().push_str(&plain_text(&el)),
+                None => {
+                    if let Some(text) = child.value().as_text() {
+                        paragraphs.last_mut().unwrap().push_str(text);
+                    }
+                }
+            }
+        }
+        paragraphs
+            .iter()
+            .map(|paragraph| paragraph.trim())
+            .filter(|paragraph| !paragraph.is_empty())
+            .collect::<Vec<&str>>()
+            .join("\n\n")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Settings of the logged-in user, as displayed on their own profile page. Obtained
+/// with [`my_profile`].
+pub struct MyProfile {
+    /// User ID (their username).
+    pub id: String,
+    /// About text, as it was typed (HackerNews formatting, not HTML).
+    pub about: String,
+    /// Email address, if the user set one.
+    pub email: Option<String>,
+    /// Whether dead (killed) stories and comments are shown.
+    pub show_dead: bool,
+    /// Whether the “noprocrast” mode is enabled, limiting the time spent on HackerNews.
+    pub no_procrast: bool,
+    /// Maximum duration of a visit in “noprocrast” mode, in minutes.
+    pub max_visit: Option<u32>,
+    /// Minimum time away between two visits in “noprocrast” mode, in minutes.
+    pub min_away: Option<u32>,
+    /// Delay before a new comment becomes visible to others, in minutes.
+    pub delay: Option<u32>,
+}
+
 #[derive(Debug)]
 /// Comment posted on a story. A comment can have a parent if it is a reply
 /// to another comment, and can have children.
@@ -90,10 +279,121 @@ pub struct Comment {
     pub date_displayed: String,
     /// HTML content of the comment.
     pub html_content: String,
-    /// Parent comment, if any.
-    pub parent: RefCell<Option<Weak<Comment>>>,
+    /// Whether the comment is a placeholder for a deleted, flagged or dead comment.
+    /// In this case `user` is empty and `html_content` contains the placeholder
+    /// (e.g. “[deleted]”).
+    pub deleted: bool,
+    /// Whether HackerNews displays the comment collapsed, i.e. without its text and
+    /// replies, until the user expands it.
+    pub collapsed: bool,
+    /// Link to edit the comment, only displayed to its author when logged in, while
+    /// HackerNews still allows editing it.
+    pub edit_url: Option<Url>,
+    /// Link to the page confirming the deletion of the comment, only displayed to its
+    /// author when logged in, while HackerNews still allows deleting it. The auth
+    /// needed to delete the comment is in the form of that page.
+    pub delete_url: Option<Url>,
+    /// Parent comment, if any. Use [`Comment::parent`] to get it.
+    parent: RefCell<Option<Weak<Comment>>>,
+    /// Reply comments. Use [`Comment::children`] to get them.
+    children: RefCell<Vec<Rc<Comment>>>,
+    /// Number of comments in the thread starting with this one, as announced by
+    /// HackerNews in the toggle collapsing it.
+    thread_size: Option<usize>,
+}
+
+impl Comment {
+    /// Reply comments.
+    pub fn children(&self) -> Vec<Rc<Comment>> {
+        self.children.borrow().clone()
+    }
+
+    /// Parent comment, if it is a reply to another comment.
+    pub fn parent(&self) -> Option<Rc<Comment>> {
+        self.parent.borrow().as_ref().and_then(Weak::upgrade)
+    }
+
+    /// Number of direct replies to the comment (not counting replies to the replies).
+    pub fn reply_count(&self) -> usize {
+        self.children.borrow().len()
+    }
+
+    /// Whether some of the replies to the comment, or replies to them, are missing,
+    /// e.g. because HackerNews collapsed them in a large thread or the comments were
+    /// truncated. Use [`expand_comment`] to get them all.
+    pub fn has_hidden_replies(&self) -> bool {
+        self.thread_size
+            .is_some_and(|size| size > 1 + self.descendant_count())
+    }
+
+    fn descendant_count(&self) -> usize {
+        self.children
+            .borrow()
+            .iter()
+            .map(|child| 1 + child.descendant_count())
+            .sum()
+    }
+
+    /// Depth of the comment in its tree: 0 for a comment without a parent, 1 for a
+    /// reply to it, and so on.
+    pub fn depth(&self) -> usize {
+        let mut depth = 0;
+        let mut parent = self.parent();
+        while let Some(comment) = parent {
+            depth += 1;
+            parent = comment.parent();
+        }
+        depth
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Comment owning its replies. Unlike [`Comment`], it has no link to its parent, but
+/// it can be sent between threads. Use [`OwnedComment::from`] to convert a comment
+/// and all its replies.
+pub struct OwnedComment {
+    /// ID of the comment.
+    pub id: u32,
+    /// User who posted the comment.
+    pub user: String,
+    /// Date the comment was posted.
+    pub date: DateTime<Utc>,
+    /// Date the comment was posted, as it is displayed, e.g. “2 months ago”.
+    pub date_displayed: String,
+    /// HTML content of the comment.
+    pub html_content: String,
+    /// Whether the comment is a placeholder for a deleted, flagged or dead comment.
+    pub deleted: bool,
+    /// Whether HackerNews displays the comment collapsed.
+    pub collapsed: bool,
+    /// Link to edit the comment, see [`Comment::edit_url`].
+    pub edit_url: Option<Url>,
+    /// Link to delete the comment, see [`Comment::delete_url`].
+    pub delete_url: Option<Url>,
     /// Reply comments.
-    pub children: RefCell<Vec<Rc<Comment>>>,
+    pub children: Vec<OwnedComment>,
+}
+
+impl From<&Comment> for OwnedComment {
+    fn from(comment: &Comment) -> OwnedComment {
+        OwnedComment {
+            id: comment.id,
+            user: comment.user.clone(),
+            date: comment.date,
+            date_displayed: comment.date_displayed.clone(),
+            html_content: comment.html_content.clone(),
+            deleted: comment.deleted,
+            collapsed: comment.collapsed,
+            edit_url: comment.edit_url.clone(),
+            delete_url: comment.delete_url.clone(),
+            children: comment
+                .children
+                .borrow()
+                .iter()
+                .map(|child| OwnedComment::from(child.as_ref()))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -101,13 +401,141 @@ pub struct Comment {
 pub struct StoryWithDetails {
     /// Information about the story.
     pub story: Story,
-    /// HTML content of the story.
+    /// HTML content of the story: the text of text stories, and the one some link
+    /// stories are submitted with.
     pub html_content: Option<String>,
     /// List of the comments posted on the story.
     pub comments: Vec<Rc<Comment>>,
+    /// Whether some comments were left out because the [`CommentFetchBudget`] was
+    /// exhausted.
+    pub truncated: bool,
+}
+
+impl StoryWithDetails {
+    /// Top-level comments, i.e. the ones posted directly on the story and not as a
+    /// reply to another comment. Their replies are available in their `children`.
+    pub fn top_level(&self) -> &[Rc<Comment>] {
+        &self.comments
+    }
+
+    /// Top-level comments with their replies, as [`OwnedComment`]s that can be sent
+    /// between threads.
+    pub fn owned_comments(&self) -> Vec<OwnedComment> {
+        self.comments
+            .iter()
+            .map(|comment| OwnedComment::from(comment.as_ref()))
+            .collect()
+    }
+
+    /// All the comments, replies included, as a flat list from the most recent to the
+    /// oldest, e.g. to see the latest replies of a long discussion.
+    ///
+    /// HackerNews only displays comments as threads, so this order loses their
+    /// structure: a reply is not next to the comment it answers anymore, and can even
+    /// come before it. The comment it answers is still available with
+    /// [`Comment::parent`]. To keep the threads, use [`sort_newest_first`](Self::sort_newest_first)
+    /// instead.
+    pub fn newest_first(&self) -> Vec<Rc<Comment>> {
+        let mut comments = vec![];
+        let mut to_visit = self.comments.clone();
+        while let Some(comment) = to_visit.pop() {
+            to_visit.extend(comment.children());
+            comments.push(comment);
+        }
+        comments.sort_by_key(|comment| Reverse(comment.date));
+        comments
+    }
+
+    /// Sort the top-level comments and the replies of each comment from the most recent
+    /// to the oldest, keeping the threads. Unlike [`newest_first`](Self::newest_first),
+    /// a recent reply to an old comment stays under it, so it isn’t among the first
+    /// comments.
+    pub fn sort_newest_first(&mut self) {
+        fn sort(comments: &mut [Rc<Comment>]) {
+            comments.sort_by_key(|comment| Reverse(comment.date));
+            for comment in comments.iter() {
+                sort(&mut comment.children.borrow_mut());
+            }
+        }
+        sort(&mut self.comments);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Current figures of a story, obtained with [`story_stats`].
+pub struct StoryStats {
+    /// Story title.
+    pub title: String,
+    /// Score of the story at this instant (`None` for job stories).
+    pub score: Option<u32>,
+    /// Number of comments posted on the story.
+    pub comment_count: Option<u32>,
+}
+
+#[derive(Debug, Default, Clone)]
+/// Limits applied when fetching the comments of a story. On long discussions, HN
+/// splits the comments over several pages, each one requiring a new request.
+pub struct CommentFetchBudget {
+    /// Maximum number of pages to fetch, including the first one. No limit if `None`.
+    pub max_pages: Option<usize>,
+    /// Maximum number of comments to collect. No limit if `None`.
+    pub max_comments: Option<usize>,
+    /// Instant after which no more pages are fetched. No limit if `None`.
+    pub deadline: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Session obtained with [`login`].
+pub struct Auth {
+    /// Cookies set by HackerNews on login, as `(name, value)` pairs.
+    pub cookies: Vec<(String, String)>,
+    /// Expiration date of the session (i.e. of the `user` cookie), if HackerNews
+    /// provided one.
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Auth {
+    /// Token to give to the functions requiring authentication, such as [`upvote_story`].
+    /// It contains all the cookies set on login, so they are all sent back to HackerNews.
+    pub fn token(&self) -> String {
+        self.cookies
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Page of a story list.
+pub struct StoryPage {
+    /// Stories of the page, by rank.
+    pub stories: HashMap<usize, Story>,
+    /// Page number, starting from 1.
+    pub page: usize,
+    /// Whether there is a page after this one.
+    pub has_more: bool,
+    /// Token to get the next page with [`next_stories_page`]. Some lists, such as
+    /// the new stories, are not paginated with a page number, making it the only
+    /// reliable way to get the next page.
+    pub more_token: Option<String>,
+}
+
+#[derive(Debug)]
+/// Page of the comments posted by a user, obtained with [`user_comments_page`].
+pub struct CommentPage {
+    /// Comments of the page, most recent first, with the replies they got.
+    pub comments: Vec<Rc<Comment>>,
+    /// Page number, starting from 1.
+    pub page: usize,
+    /// Whether there is a page after this one.
+    pub has_more: bool,
+    /// Token to get the next page with [`next_comments_page`].
+    pub more_token: Option<String>,
 }
 
 /// Available story lists.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum StoryList {
     /// Top stories.
     News,
@@ -136,6 +564,57 @@ impl StoryList {
     }
 }
 
+#[derive(Debug)]
+/// Error returned by the functions of this crate.
+pub enum HnError {
+    /// The request to HackerNews failed.
+    #[cfg(feature = "fetch")]
+    Request(reqwest::Error),
+    /// The auth token is invalid or expired.
+    Unauthenticated,
+    /// HackerNews refused the submitted data, with the message it displayed (e.g.
+    /// “Unknown or expired link.”).
+    Rejected(String),
+    /// HackerNews is refusing to serve more requests for now. Callers should wait before
+    /// making new ones.
+    RateLimited,
+    /// HackerNews answered with an error status, e.g. 404 or 503.
+    Status(u16),
+}
+
+impl fmt::Display for HnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            #[cfg(feature = "fetch")]
+            HnError::Request(err) => write!(f, "Request to HackerNews failed: {}", err),
+            HnError::Unauthenticated => write!(f, "Invalid or expired auth token"),
+            HnError::Rejected(message) => write!(f, "Rejected by HackerNews: {}", message),
+            HnError::RateLimited => write!(f, "Too many requests to HackerNews, try again later"),
+            HnError::Status(status) => write!(f, "HackerNews answered with status {}", status),
+        }
+    }
+}
+
+impl Error for HnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            #[cfg(feature = "fetch")]
+            HnError::Request(err) => Some(err),
+            HnError::Unauthenticated
+            | HnError::Rejected(_)
+            | HnError::RateLimited
+            | HnError::Status(_) => None,
+        }
+    }
+}
+
+#[cfg(feature = "fetch")]
+impl From<reqwest::Error> for HnError {
+    fn from(err: reqwest::Error) -> HnError {
+        HnError::Request(err)
+    }
+}
+
 /// Get all the stories for a given list at a given page.
 ///
 /// ## Example
@@ -154,28 +633,97 @@ impl StoryList {
 ///     Ok(())
 /// }
 /// ```
+#[cfg(feature = "fetch")]
 pub async fn stories_list(
     list: StoryList,
     page: usize,
     token: &Option<String>,
-) -> Result<HashMap<usize, Story>, Box<dyn Error>> {
+) -> Result<HashMap<usize, Story>, HnError> {
+    Ok(stories_page(list, page, token).await?.stories)
+}
+
+/// Get the stories of the first page of a list that are not among known ones, e.g. the
+/// ones already seen in a previous call, to know which stories appeared since then.
+/// Ranks are the ones of the stories in the list.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{stories_since, StoryList};
+/// use std::collections::HashSet;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let mut known_ids = HashSet::new();
+///     let stories = stories_since(StoryList::Newest, &known_ids, &None).await?;
+///     known_ids.extend(stories.values().map(|story| story.id));
+///     let fresh_stories = stories_since(StoryList::Newest, &known_ids, &None).await?;
+///     assert!(fresh_stories.len() < 30);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn stories_since(
+    list: StoryList,
+    known_ids: &HashSet<u32>,
+    token: &Option<String>,
+) -> Result<HashMap<usize, Story>, HnError> {
+    let mut stories = stories_list(list, 1, token).await?;
+    stories.retain(|_, story| !known_ids.contains(&story.id));
+    Ok(stories)
+}
+
+/// Get all the stories for a given list at a given page, with information about the
+/// next page.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{next_stories_page, stories_page, StoryList};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let page = stories_page(StoryList::Newest, 1, &None).await?;
+///     assert_eq!(page.stories.len(), 30);
+///     assert!(page.has_more);
+///     let next_page = next_stories_page(&page, &None).await?.unwrap();
+///     assert_eq!(next_page.page, 2);
+///     assert!(next_page.stories.get(&31).is_some());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn stories_page(
+    list: StoryList,
+    page: usize,
+    token: &Option<String>,
+) -> Result<StoryPage, HnError> {
     let url = format!("{}?p={}", list.url(), page);
     let document = document_at_url(&url, token).await?;
-    let stories: HashMap<usize, Story> = document
-        .select(&Selector::parse("tr.athing").unwrap())
-        .map(|tr| {
-            let rank = single_element_html(&tr, ".rank")
-                .map(|rank| rank.replace(".", "").parse::<usize>().unwrap())
-                .unwrap();
-            let story = extract_story_info(&tr);
-            (rank, story)
-        })
-        .collect();
-    Ok(stories)
+    Ok(story_page_from_document(&document, page))
+}
+
+/// Get the page following a given page of stories, using its `more_token`. Will return
+/// `None` if it was the last page.
+#[cfg(feature = "fetch")]
+pub async fn next_stories_page(
+    previous: &StoryPage,
+    token: &Option<String>,
+) -> Result<Option<StoryPage>, HnError> {
+    if let Some(more_token) = previous.more_token.as_ref() {
+        let url = format!("{}/{}", BASE_URL, more_token);
+        let document = document_at_url(&url, token).await?;
+        Ok(Some(story_page_from_document(&document, previous.page + 1)))
+    } else {
+        Ok(None)
+    }
 }
 
 /// Get the details about a given story. Will return `null` for a non-existent story ID.
 ///
+/// On long discussions, HN splits the comments over several pages. All of them are
+/// fetched; use [`story_details_with_budget`] to bound the number of requests.
+///
 /// ## Example
 ///
 /// ```
@@ -194,198 +742,1384 @@ pub async fn stories_list(
 ///     Ok(())
 /// }
 /// ```
-pub async fn story_details(id: u32) -> Result<Option<StoryWithDetails>, Box<dyn Error>> {
-    let url = format!("{}/item?id={}", BASE_URL, id);
-    let document = document_at_url(&url, &None).await?;
-    if let Some(tr) = single_doc_element(&document, "table.fatitem tr.athing") {
-        let story = extract_story_info(&tr);
-
-        let html_content = tr
-            .next_sibling()
-            .and_then(|el| el.next_sibling())
-            .and_then(|el| el.next_sibling())
-            .and_then(|el| el.next_sibling())
-            .and_then(|el| el.first_child())
-            .and_then(|el| el.next_sibling())
-            .and_then(ElementRef::wrap)
-            .map(|el| el.inner_html())
-            .filter(|html| !html.contains("<form "));
-
-        // let mut comments_map: HashMap<u32, Comment> = HashMap::new();
-        // let mut comments_ids_with_indents: Vec<(usize, u32)> = vec![];
-        let comments_selector = Selector::parse(".comment-tree tr.comtr").unwrap();
-        let comment_trs = document.select(&comments_selector);
-        let mut comments: Vec<Rc<Comment>> = vec![];
-        let mut parent_stack: Vec<Rc<Comment>> = vec![];
-        for comment_tr in comment_trs {
-            let ind_selector = Selector::parse(".ind").unwrap();
-            let indent = comment_tr
-                .select(&ind_selector)
-                .next()
-                .and_then(|ind| ind.value().attr("indent"))
-                .map(|ind| ind.parse::<usize>().unwrap())
-                .unwrap_or(0);
-            let comment = Rc::new(extract_comment_info(&comment_tr));
-
-            while indent < parent_stack.len() {
-                parent_stack.pop();
-            }
-
-            if indent == 0 {
-                comments.push(Rc::clone(&comment));
-                parent_stack.push(Rc::clone(&comment));
-            } else {
-                let parent = parent_stack.pop().unwrap();
-                (*parent.children.borrow_mut()).push(Rc::clone(&comment));
-                (*comment.parent.borrow_mut()) = Some(Rc::downgrade(&parent));
-                parent_stack.push(parent);
-                parent_stack.push(comment);
-            }
-        }
+#[cfg(feature = "fetch")]
+pub async fn story_details(id: u32) -> Result<Option<StoryWithDetails>, HnError> {
+    story_details_with_budget(id, &CommentFetchBudget::default()).await
+}
 
-        let story_details = StoryWithDetails {
-            story,
-            html_content,
-            comments,
-        };
-        Ok(Some(story_details))
-    } else {
-        Ok(None)
+/// Get the details about a given story, stopping to fetch comments once the budget
+/// is exhausted. In this case, the comments collected so far are returned, and the
+/// `truncated` flag is set on the result.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{story_details_with_budget, CommentFetchBudget};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let budget = CommentFetchBudget {
+///         max_comments: Some(5),
+///         ..CommentFetchBudget::default()
+///     };
+///     let details = story_details_with_budget(29203502, &budget).await?.unwrap();
+///     assert!(details.truncated);
+///     assert!(details.comments.len() <= 5);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn story_details_with_budget(
+    id: u32,
+    budget: &CommentFetchBudget,
+) -> Result<Option<StoryWithDetails>, HnError> {
+    let url = format!("{}/item?id={}", BASE_URL, id);
+    let (pages, truncated) = item_pages(&url, budget).await?;
+    let details = story_details_from_pages(&pages, budget.max_comments, truncated);
+    if details.is_none() {
+        debug!("No story found at {}", url);
     }
+    Ok(details)
 }
 
-/// Get the details about a given user. Will return `null` for a non-existent user ID.
+/// Maximum number of stories requested at the same time by [`stories_details`].
+#[cfg(feature = "fetch")]
+const MAX_CONCURRENT_STORY_REQUESTS: usize = 4;
+
+/// Get the details about several stories at once, e.g. to prefetch the first stories of
+/// a list. The stories are requested concurrently, a few at a time. The details are
+/// returned in the order of the IDs, with `None` for non-existent story IDs.
 ///
 /// ## Example
 ///
 /// ```
-/// use hnapi::user_details;
+/// use hnapi::stories_details;
 ///
 /// #[tokio::main]
 /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-///     let user = user_details("scastiel").await?;
-///     assert!(user.is_some());
-///     let user = user.unwrap();
-///     println!("{:#?}", user);
-///     assert_eq!(user.id, "scastiel".to_string());
+///     let stories = stories_details(&[29203502, 27883047]).await?;
+///     assert_eq!(stories.len(), 2);
+///     assert_eq!(stories[1].as_ref().unwrap().story.id, 27883047);
 ///     Ok(())
 /// }
 /// ```
-pub async fn user_details(id: &str) -> Result<Option<User>, Box<dyn Error>> {
-    let url = format!("{}/user?id={}", BASE_URL, id);
-    let document = document_at_url(&url, &None).await?;
-    if let Some(table) =
-        single_doc_element(&document, "#hnmain > tbody > tr:nth-child(3) > td > table")
-    {
-        let id = single_element_html(&table, "tr:nth-child(1) .hnuser").unwrap();
-
-        let created = single_element(&table, "tr:nth-child(2) > td:nth-child(2) > a")
-            .and_then(|a| a.value().attr("href"))
-            .map(|href| {
-                let caps = Regex::new(r"(?P<date>\d{4}-\d{2}-\d{2})")
-                    .unwrap()
-                    .captures(href)
-                    .unwrap();
-                NaiveDate::from_str(&caps["date"]).unwrap()
+#[cfg(feature = "fetch")]
+pub async fn stories_details(ids: &[u32]) -> Result<Vec<Option<StoryWithDetails>>, HnError> {
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_STORY_REQUESTS));
+    // Only the pages are fetched in the tasks, as the comments can't be sent between
+    // threads once parsed.
+    let requests: Vec<_> = ids
+        .iter()
+        .map(|&id| {
+            let permits = permits.clone();
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let url = format!("{}/item?id={}", BASE_URL, id);
+                item_pages(&url, &CommentFetchBudget::default()).await
             })
-            .unwrap();
+        })
+        .collect();
+    let mut stories = Vec::with_capacity(ids.len());
+    for request in requests {
+        let (pages, truncated) = request.await.expect("Fetching a story doesn’t panic")?;
+        stories.push(story_details_from_pages(&pages, None, truncated));
+    }
+    Ok(stories)
+}
 
-        let karma = single_element_html(&table, "tr:nth-child(3) > td:nth-child(2)")
-            .map(|karma| karma.trim().parse().unwrap())
-            .unwrap();
-        let about = single_element_html(&table, "tr:nth-child(4) > td:nth-child(2)")
-            .map(|about| about.trim().to_string())
-            .unwrap();
+/// Story and comments from the HTML of the pages of a story, the first one being the
+/// page of the story itself. The comments after `max_comments` are left out, in which
+/// case the result is marked as truncated, as it is if `truncated` is `true` (e.g.
+/// because some pages were not fetched).
+fn story_details_from_pages(
+    pages: &[String],
+    max_comments: Option<usize>,
+    mut truncated: bool,
+) -> Option<StoryWithDetails> {
+    let document = Html::parse_document(pages.first()?);
+    let tr = single_doc_element(&document, "table.fatitem tr.athing")?;
+    let story = extract_story_info(&tr)?;
+    // Text stories (e.g. “Ask HN”) have a text, but link stories can have one too.
+    let html_content = story_text(&document);
 
-        return Ok(Some(User {
-            id,
-            created,
-            karma,
-            about,
-        }));
+    let mut comments: Vec<Rc<Comment>> = vec![];
+    let mut parent_stack: Vec<Rc<Comment>> = vec![];
+    let mut comment_count = 0;
+    for page in pages {
+        let document = Html::parse_document(page);
+        if extend_comment_tree(
+            &document,
+            &mut comments,
+            &mut parent_stack,
+            &mut comment_count,
+            max_comments,
+        ) {
+            truncated = true;
+            break;
+        }
     }
-    Ok(None)
+
+    Some(StoryWithDetails {
+        story,
+        html_content,
+        comments,
+        truncated,
+    })
 }
 
-async fn document_at_url(url: &str, token: &Option<String>) -> Result<Html, reqwest::Error> {
-    let client = reqwest::ClientBuilder::new().build()?;
-    let mut request_builder = client.get(url);
-    if let Some(token) = token {
-        request_builder = request_builder.header(COOKIE, format!("user={}", token));
+/// Get the details about a given story like [`story_details_with_budget`] does, and
+/// optionally replace its score and comment count with the ones of the
+/// [official API](https://github.com/HackerNews/API), which are exact even when
+/// HackerNews rounds or hides them on the page (e.g. for job offers).
+///
+/// Enriching the story costs an extra request to the official API (the comments are
+/// still scraped, since it only gives their IDs). If it fails, the whole call fails.
+/// Requires the `firebase` feature.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{story_details_with_options, CommentFetchBudget};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let budget = CommentFetchBudget::default();
+///     let details = story_details_with_options(29203502, &budget, true).await?.unwrap();
+///     assert!(details.story.score.is_some());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "firebase")]
+pub async fn story_details_with_options(
+    id: u32,
+    budget: &CommentFetchBudget,
+    enrich_with_firebase: bool,
+) -> Result<Option<StoryWithDetails>, HnError> {
+    let mut details = story_details_with_budget(id, budget).await?;
+    if let (Some(details), true) = (details.as_mut(), enrich_with_firebase) {
+        if let Some(item) = firebase_item(id).await? {
+            enrich_story(&mut details.story, &item);
+        }
     }
-    let resp = request_builder.send().await?;
-    let html = resp.text().await?;
-    Ok(Html::parse_document(&html))
+    Ok(details)
 }
 
-pub async fn login(
-    username: &str,
-    password: &str,
-) -> Result<Option<(String, DateTime<Utc>)>, reqwest::Error> {
-    let client = reqwest::ClientBuilder::new()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-    let url = format!("{}/login", BASE_URL);
-    let body = url::form_urlencoded::Serializer::new(String::new())
-        .append_pair("goto", "news")
-        .append_pair("acct", username)
-        .append_pair("pw", password)
-        .finish();
-    let response = client
-        .post(url)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(body)
+/// Fields of an item of the official API used to enrich scraped stories.
+#[cfg(feature = "firebase")]
+#[derive(Deserialize)]
+struct FirebaseItem {
+    score: Option<u32>,
+    descendants: Option<u32>,
+}
+
+/// Item from the official API, or `None` if it doesn’t exist (the API answers `null`).
+#[cfg(feature = "firebase")]
+async fn firebase_item(id: u32) -> Result<Option<FirebaseItem>, HnError> {
+    let url = format!("{}/item/{}.json", FIREBASE_URL, id);
+    let client = client_builder().build()?;
+    debug!("GET {}", url);
+    let start = Instant::now();
+    let resp = client
+        .get(&url)
         .send()
-        .await?;
-    let token = response.cookies().next().map(|cookie| {
-        let token = cookie.value().to_string();
-        let expires = cookie.expires().map(DateTime::<Utc>::from).unwrap();
-        (token, expires)
-    });
-    Ok(token)
+        .await
+        .inspect_err(|err| warn!("GET {} failed: {}", url, err))?;
+    debug!("GET {} -> {} in {:?}", url, resp.status(), start.elapsed());
+    Ok(resp.json().await?)
 }
 
-pub async fn upvote_story(id: u32, upvote_auth: &str, token: &str) -> Result<bool, reqwest::Error> {
-    let url = format!(
-        "{}/vote?id={}&how=up&auth={}&goto=news",
-        BASE_URL, id, upvote_auth
-    );
-    let document = document_at_url(&url, &Some(token.to_string())).await?;
-    if single_doc_element(&document, "form[action='vote']").is_some() {
-        return Ok(false);
-    }
-    Ok(true)
+/// Replace the score and comment count of a story with the ones of the official API,
+/// keeping the scraped ones when the API doesn’t provide them.
+#[cfg(feature = "firebase")]
+fn enrich_story(story: &mut Story, item: &FirebaseItem) {
+    story.score = item.score.or(story.score);
+    story.comment_count = item.descendants.or(story.comment_count);
 }
 
-fn extract_story_info(first_line_el: &ElementRef) -> Story {
-    let id = first_line_el.value().attr("id").unwrap().parse().unwrap();
-    let title_el = single_element(first_line_el, ".titlelink").unwrap();
-    let (title, url) = link_info(&title_el);
-    let url_displayed = single_element_html(first_line_el, ".sitestr");
-    let upvote_auth = single_element(first_line_el, ".clicky").and_then(|upvote_link| {
-        let (_, upvote_url) = link_info(&upvote_link);
-        upvote_url.query_pairs().find_map(|(key, value)| {
-            if key == "auth" {
-                Some(value.to_string())
-            } else {
-                None
+/// HTML of the pages of an item (story or comment), following the “More” links at the
+/// bottom of each page while the budget allows it. Also returns whether some pages
+/// were left out.
+#[cfg(feature = "fetch")]
+async fn item_pages(
+    url: &str,
+    budget: &CommentFetchBudget,
+) -> Result<(Vec<String>, bool), HnError> {
+    let mut pages = vec![html_at_url(url, &None).await?];
+    let mut comment_count = 0;
+    let mut truncated = false;
+    loop {
+        let (page_comment_count, more_url) = comments_page_info(pages.last().unwrap());
+        comment_count += page_comment_count;
+        let more_url = match more_url {
+            Some(more_url) => more_url,
+            None => break,
+        };
+        if budget.max_pages.is_some_and(|max| pages.len() >= max)
+            || budget.max_comments.is_some_and(|max| comment_count >= max)
+        {
+            debug!("Comment fetch budget exhausted, not fetching {}", more_url);
+            truncated = true;
+            break;
+        }
+        let html = match budget.deadline {
+            Some(deadline) => {
+                let deadline = tokio::time::Instant::from_std(deadline);
+                match tokio::time::timeout_at(deadline, html_at_url(more_url.as_str(), &None)).await
+                {
+                    Ok(html) => html?,
+                    Err(_) => {
+                        warn!("Deadline reached while fetching {}", more_url);
+                        truncated = true;
+                        break;
+                    }
+                }
             }
-        })
-    });
-
-    let second_line_el = ElementRef::wrap(first_line_el.next_sibling().unwrap()).unwrap();
-    let score = single_element_html(&second_line_el, ".score").map(parse_score);
-    let user = single_element_html(&second_line_el, ".hnuser");
-    let (date, date_displayed) = single_element(&second_line_el, ".age")
-        .map(|d| date_info(&d))
-        .unwrap();
+            None => html_at_url(more_url.as_str(), &None).await?,
+        };
+        pages.push(html);
+    }
+    Ok((pages, truncated))
+}
 
+/// Get the title, score and comment count of a story, e.g. to follow how a story is
+/// doing. Unlike [`story_details`], only the first page of the story is requested and its
+/// comments are not parsed. Will return `None` for a non-existent story ID.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::story_stats;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let stats = story_stats(27883047).await?.unwrap();
+///     assert_eq!(stats.title, "Julia Computing raises $24M Series A");
+///     assert!(stats.score.is_some());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn story_stats(id: u32) -> Result<Option<StoryStats>, HnError> {
+    let url = format!("{}/item?id={}", BASE_URL, id);
+    let html = html_at_url(&url, &None).await?;
+    let stats = story_stats_from_document(&Html::parse_document(&html));
+    if stats.is_none() {
+        debug!("No story found at {}", url);
+    }
+    Ok(stats)
+}
+
+fn story_stats_from_document(document: &Html) -> Option<StoryStats> {
+    let tr = single_doc_element(document, "table.fatitem tr.athing")?;
+    // Comments have an `athing` row too, but no title.
+    single_element(&tr, ".titlelink")?;
+    let story = extract_story_info(&tr)?;
+    Some(StoryStats {
+        title: story.title,
+        score: story.score,
+        comment_count: story.comment_count,
+    })
+}
+
+/// Estimate the time needed to read a story in minutes, at 200 words per minute: the
+/// time to read the article it links to, or its own text for text posts. This costs one
+/// more request, to the website of the article or to HackerNews for text posts. Will
+/// return `None` if the story has no text, or if no paragraphs were found in the article.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{story_details, story_reading_time};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let story = story_details(27883047).await?.unwrap().story;
+///     let minutes = story_reading_time(&story).await?;
+///     assert!(minutes.is_none_or(|minutes| minutes > 0));
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn story_reading_time(story: &Story) -> Result<Option<u32>, HnError> {
+    let word_count = if story.is_text_post() {
+        let budget = CommentFetchBudget {
+            max_pages: Some(1),
+            max_comments: Some(0),
+            deadline: None,
+        };
+        story_details_with_budget(story.id, &budget)
+            .await?
+            .and_then(|details| details.html_content)
+            .map_or(0, |text| text_word_count(&text))
+    } else {
+        let html = html_at_url(story.url.as_str(), &None).await?;
+        article_word_count_from_document(&Html::parse_document(&html))
+    };
+    Ok(match word_count {
+        0 => None,
+        word_count => Some(reading_time(word_count)),
+    })
+}
+
+/// Minutes needed to read a text of `word_count` words at 200 words per minute, at
+/// least 1, e.g. with the count given by [`text_word_count`] or
+/// [`parse::article_word_count`].
+pub fn reading_time(word_count: usize) -> u32 {
+    word_count.div_ceil(WORDS_PER_MINUTE).max(1) as u32
+}
+
+/// Number of words of a text as HackerNews serves it, e.g. the `html_content` of a story
+/// or a comment.
+pub fn text_word_count(html_content: &str) -> usize {
+    let fragment = Html::parse_fragment(html_content);
+    count_words(fragment.root_element().text())
+}
+
+/// Number of words of the main text of an article: the paragraphs of its `article`
+/// element (or `main`, or `body` otherwise), so that menus and footers are left out.
+fn article_word_count_from_document(document: &Html) -> usize {
+    let main = ["article", "main", "body"]
+        .iter()
+        .find_map(|selector| single_doc_element(document, selector));
+    let paragraph_selector = Selector::parse("p").unwrap();
+    main.map_or(0, |main| {
+        count_words(main.select(&paragraph_selector).flat_map(|p| p.text()))
+    })
+}
+
+fn count_words<'a>(texts: impl Iterator<Item = &'a str>) -> usize {
+    texts.map(|text| text.split_whitespace().count()).sum()
+}
+
+/// Get a comment and its replies, e.g. to display a single thread from a link to the
+/// comment. Will return `None` for a non-existent comment ID, or if the ID is the one of
+/// a story.
+///
+/// Only the replies displayed on the first page of the comment are returned.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{comment_thread, story_details};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let details = story_details(27883047).await?.unwrap();
+///     let id = details.comments[0].id;
+///     let comment = comment_thread(id).await?.unwrap();
+///     assert_eq!(comment.id, id);
+///     assert!(comment
+///         .children()
+///         .iter()
+///         .all(|child| child.parent().unwrap().id == comment.id));
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn comment_thread(id: u32) -> Result<Option<Rc<Comment>>, HnError> {
+    let url = format!("{}/item?id={}", BASE_URL, id);
+    let html = html_at_url(&url, &None).await?;
+    let comment = comment_thread_from_document(&Html::parse_document(&html));
+    if comment.is_none() {
+        debug!("No comment found at {}", url);
+    }
+    Ok(comment)
+}
+
+/// Get a comment and all its replies, including the ones HackerNews collapses or
+/// displays on other pages in large threads, e.g. to expand a comment for which
+/// [`Comment::has_hidden_replies`] is `true`. Will return `None` for a non-existent
+/// comment ID, or if the ID is the one of a story.
+///
+/// Unlike [`comment_thread`], all the pages of the comment are requested, which can take
+/// some time for the first comments of a large discussion.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{expand_comment, story_details};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let details = story_details(27883047).await?.unwrap();
+///     let id = details.comments[0].id;
+///     let comment = expand_comment(id).await?.unwrap();
+///     assert_eq!(comment.id, id);
+///     assert!(!comment.has_hidden_replies());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn expand_comment(id: u32) -> Result<Option<Rc<Comment>>, HnError> {
+    let url = format!("{}/item?id={}", BASE_URL, id);
+    let (pages, _) = item_pages(&url, &CommentFetchBudget::default()).await?;
+    let documents: Vec<Html> = pages
+        .iter()
+        .map(|page| Html::parse_document(page))
+        .collect();
+    let comment = comment_thread_from_documents(&documents);
+    if comment.is_none() {
+        debug!("No comment found at {}", url);
+    }
+    Ok(comment)
+}
+
+fn comment_thread_from_document(document: &Html) -> Option<Rc<Comment>> {
+    comment_thread_from_documents(std::slice::from_ref(document))
+}
+
+/// Comment displayed on the first page, with the replies of all the pages.
+fn comment_thread_from_documents(documents: &[Html]) -> Option<Rc<Comment>> {
+    let tr = single_doc_element(documents.first()?, "table.fatitem tr.athing")?;
+    if single_element(&tr, ".titlelink").is_some() {
+        return None;
+    }
+    let comment = Rc::new(extract_comment_info(&tr)?);
+    // Replies are displayed as top-level comments, starting with no indentation.
+    let mut replies = vec![];
+    let mut parent_stack = vec![];
+    for document in documents {
+        extend_comment_tree(document, &mut replies, &mut parent_stack, &mut 0, None);
+    }
+    for reply in replies {
+        (*reply.parent.borrow_mut()) = Some(Rc::downgrade(&comment));
+        comment.children.borrow_mut().push(reply);
+    }
+    Some(comment)
+}
+
+/// Get the details about a given user. Will return `null` for a non-existent user ID.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::user_details;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let user = user_details("scastiel").await?;
+///     assert!(user.is_some());
+///     let user = user.unwrap();
+///     println!("{:#?}", user);
+///     assert_eq!(user.id, "scastiel".to_string());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_details(id: &str) -> Result<Option<User>, HnError> {
+    let url = format!("{}/user?id={}", BASE_URL, id);
+    let document = document_at_url(&url, &None).await?;
+    let user = user_from_document(&document);
+    if user.is_none() {
+        debug!("No user found at {}", url);
+    }
+    Ok(user)
+}
+
+fn user_from_document(document: &Html) -> Option<User> {
+    let table = single_doc_element(document, "#hnmain > tbody > tr:nth-child(3) > td > table")?;
+    let id = single_element_html(&table, "tr:nth-child(1) .hnuser").unwrap();
+
+    let created = single_element(&table, "tr:nth-child(2) > td:nth-child(2) > a")
+        .and_then(|a| a.value().attr("href"))
+        .map(|href| {
+            let caps = Regex::new(r"(?P<date>\d{4}-\d{2}-\d{2})")
+                .unwrap()
+                .captures(href)
+                .unwrap();
+            NaiveDate::from_str(&caps["date"]).unwrap()
+        })
+        .unwrap();
+
+    let karma = single_element_html(&table, "tr:nth-child(3) > td:nth-child(2)")
+        .map(|karma| karma.trim().parse().unwrap())
+        .unwrap();
+    let about = single_element_html(&table, "tr:nth-child(4) > td:nth-child(2)")
+        .map(|about| about.trim().to_string())
+        .unwrap();
+
+    Some(User {
+        id,
+        created,
+        karma,
+        about,
+    })
+}
+
+/// Maximum number of user pages requested at the same time by [`users`].
+#[cfg(feature = "fetch")]
+const MAX_CONCURRENT_USER_REQUESTS: usize = 4;
+
+/// Get the details about several users at once, e.g. the participants of a thread, by
+/// ID. The users are requested concurrently, a few at a time. Non-existent users are
+/// not in the returned map.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::users;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let users = users(&["scastiel", "dang"]).await?;
+///     assert_eq!(users.len(), 2);
+///     assert_eq!(users["dang"].id, "dang".to_string());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn users(ids: &[&str]) -> Result<HashMap<String, User>, HnError> {
+    let ids: HashSet<&str> = ids.iter().copied().collect();
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_USER_REQUESTS));
+    let requests: Vec<_> = ids
+        .into_iter()
+        .map(|id| {
+            let id = id.to_string();
+            let permits = permits.clone();
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let user = user_details(&id).await;
+                (id, user)
+            })
+        })
+        .collect();
+    let mut users = HashMap::new();
+    for request in requests {
+        let (id, user) = request.await.expect("Fetching a user doesn’t panic");
+        if let Some(user) = user? {
+            users.insert(id, user);
+        }
+    }
+    Ok(users)
+}
+
+/// HTTP client builder using the TLS backend selected with the `native-tls` and
+/// `rustls` features. Responses are compressed when the server supports it, which makes
+/// the pages of large discussions much smaller.
+#[cfg(feature = "fetch")]
+fn client_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::ClientBuilder::new().gzip(true).deflate(true);
+    #[cfg(feature = "rustls")]
+    let builder = builder.use_rustls_tls();
+    #[cfg(all(feature = "native-tls", not(feature = "rustls")))]
+    let builder = builder.use_native_tls();
+    builder
+}
+
+/// Value of the `Cookie` header for a token. Tokens returned by [`Auth::token`] contain
+/// all the cookies, while a bare value is the one of the `user` cookie.
+#[cfg(feature = "fetch")]
+fn cookie_header(token: &str) -> String {
+    if token.contains('=') {
+        token.to_string()
+    } else {
+        format!("user={}", token)
+    }
+}
+
+/// Get the stories submitted by a user, most recent first. Will return an empty
+/// map for a non-existent user ID.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::user_submissions;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let stories = user_submissions("scastiel", &None).await?;
+///     assert!(!stories.is_empty());
+///     assert!(stories
+///         .values()
+///         .all(|story| story.user == Some("scastiel".to_string())));
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_submissions(
+    id: &str,
+    token: &Option<String>,
+) -> Result<HashMap<usize, Story>, HnError> {
+    Ok(user_submissions_page(id, token).await?.stories)
+}
+
+/// Get the first page of the stories submitted by a user, most recent first, with
+/// information about the next page. Use [`next_stories_page`] to get the next ones,
+/// e.g. to go through the whole history of a user.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{next_stories_page, user_submissions_page};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let page = user_submissions_page("pg", &None).await?;
+///     assert!(page.has_more);
+///     let next_page = next_stories_page(&page, &None).await?.unwrap();
+///     assert_eq!(next_page.page, 2);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_submissions_page(id: &str, token: &Option<String>) -> Result<StoryPage, HnError> {
+    let url = format!("{}/submitted?id={}", BASE_URL, id);
+    let document = document_at_url(&url, token).await?;
+    Ok(story_page_from_document(&document, 1))
+}
+
+/// Get the comments posted by a user, most recent first, with the replies they got.
+/// Will return an empty list for a non-existent user ID.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::user_comments;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let comments = user_comments("scastiel").await?;
+///     assert!(!comments.is_empty());
+///     assert!(comments
+///         .iter()
+///         .all(|comment| comment.user == "scastiel" || comment.deleted));
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_comments(id: &str) -> Result<Vec<Rc<Comment>>, HnError> {
+    Ok(user_comments_page(id).await?.comments)
+}
+
+/// Get the first page of the comments posted by a user, most recent first, with
+/// information about the next page. Use [`next_comments_page`] to get the next ones.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::{next_comments_page, user_comments_page};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let page = user_comments_page("pg").await?;
+///     assert!(page.has_more);
+///     let next_page = next_comments_page(&page).await?.unwrap();
+///     assert_eq!(next_page.page, 2);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_comments_page(id: &str) -> Result<CommentPage, HnError> {
+    let url = format!("{}/threads?id={}", BASE_URL, id);
+    let document = document_at_url(&url, &None).await?;
+    Ok(comment_page_from_document(&document, 1))
+}
+
+/// Get the page following a given page of comments, using its `more_token`. Will return
+/// `None` if it was the last page.
+#[cfg(feature = "fetch")]
+pub async fn next_comments_page(previous: &CommentPage) -> Result<Option<CommentPage>, HnError> {
+    if let Some(more_token) = previous.more_token.as_ref() {
+        let url = format!("{}/{}", BASE_URL, more_token);
+        let document = document_at_url(&url, &None).await?;
+        Ok(Some(comment_page_from_document(
+            &document,
+            previous.page + 1,
+        )))
+    } else {
+        Ok(None)
+    }
+}
+
+fn comment_page_from_document(document: &Html, page: usize) -> CommentPage {
+    let mut comments = vec![];
+    extend_comment_tree(document, &mut comments, &mut vec![], &mut 0, None);
+    let more_token = more_token(document);
+    CommentPage {
+        comments,
+        page,
+        has_more: more_token.is_some(),
+        more_token,
+    }
+}
+
+/// Get the number of stories and comments a user posted per day, oldest day first.
+/// Days without any post are omitted.
+///
+/// HackerNews doesn’t expose the history of a user’s karma or activity, so this is
+/// derived from the first page of their submissions and of their comments. It only
+/// covers their most recent posts, and gives an idea of how often they post rather
+/// than an authoritative history.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::user_activity;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let activity = user_activity("scastiel").await?;
+///     assert!(activity.iter().all(|(_, count)| *count > 0));
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn user_activity(id: &str) -> Result<Vec<(NaiveDate, u32)>, HnError> {
+    let submissions_url = format!("{}/submitted?id={}", BASE_URL, id);
+    let submissions_html = html_at_url(&submissions_url, &None).await?;
+    let comments_url = format!("{}/threads?id={}", BASE_URL, id);
+    let comments_html = html_at_url(&comments_url, &None).await?;
+
+    let submissions = story_page_from_document(&Html::parse_document(&submissions_html), 1).stories;
+    let mut comments = vec![];
+    extend_comment_tree(
+        &Html::parse_document(&comments_html),
+        &mut comments,
+        &mut vec![],
+        &mut 0,
+        None,
+    );
+
+    let mut dates: Vec<DateTime<Utc>> = submissions.values().map(|story| story.date).collect();
+    collect_comment_dates(&comments, id, &mut dates);
+    Ok(activity_by_day(&dates))
+}
+
+/// Selectors the parser relies on to read the stories of a list, checked by
+/// [`check_markup`].
+const STORY_LIST_SELECTORS: &[&str] = &[
+    "tr.athing",
+    "tr.athing .rank",
+    "tr.athing .titlelink",
+    ".subtext .score",
+    ".subtext .hnuser",
+    ".subtext .age",
+];
+
+/// Result of [`check_markup`].
+#[derive(Debug, Default)]
+pub struct MarkupHealth {
+    /// Selectors the parser relies on that didn’t match anything on the front page.
+    pub missing_selectors: Vec<String>,
+}
+
+impl MarkupHealth {
+    /// Whether all the selectors were found, i.e. the parser should still work.
+    pub fn is_healthy(&self) -> bool {
+        self.missing_selectors.is_empty()
+    }
+}
+
+/// Check that HackerNews is reachable and that its markup is still the one this crate
+/// expects, by looking for the elements it relies on in the front page. Since the
+/// crate scrapes the website, a redesign would break it: this can be run regularly
+/// (e.g. in a CI job) to know early when the parser needs to be updated.
+///
+/// Will return an error if HackerNews can’t be reached.
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::check_markup;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let health = check_markup().await?;
+///     assert!(health.is_healthy(), "missing: {:?}", health.missing_selectors);
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn check_markup() -> Result<MarkupHealth, HnError> {
+    let url = format!("{}/news", BASE_URL);
+    let html = html_at_url(&url, &None).await?;
+    Ok(markup_health(&Html::parse_document(&html)))
+}
+
+fn markup_health(document: &Html) -> MarkupHealth {
+    let missing_selectors = STORY_LIST_SELECTORS
+        .iter()
+        .filter(|selector| single_doc_element(document, selector).is_none())
+        .map(ToString::to_string)
+        .collect();
+    MarkupHealth { missing_selectors }
+}
+
+/// Get the username of the user an auth token belongs to. Will return `None` if the
+/// token is invalid or expired.
+#[cfg(feature = "fetch")]
+pub async fn current_user(token: &str) -> Result<Option<String>, HnError> {
+    let url = format!("{}/news", BASE_URL);
+    let document = document_at_url(&url, &Some(token.to_string())).await?;
+    Ok(single_doc_element(&document, "#me").map(|me| me.inner_html()))
+}
+
+/// Get the number of replies to the comments of the user an auth token belongs to,
+/// posted after their latest comment. HackerNews has no notifications and doesn’t keep
+/// track of the replies a user has read, so replying is taken as having read the
+/// previous ones; use [`replies_since`] to remember when the user last checked instead.
+///
+/// Only the replies displayed on the first page of the user’s threads are counted.
+/// Will return an [`HnError::Unauthenticated`] error if the token is invalid or expired.
+#[cfg(feature = "fetch")]
+pub async fn unread_replies(token: &str) -> Result<u32, HnError> {
+    let (username, comments) = my_threads(token).await?;
+    let mut dates = vec![];
+    collect_comment_dates(&comments, &username, &mut dates);
+    Ok(match dates.into_iter().max() {
+        Some(latest) => count_replies(&comments, &username, &latest),
+        None => 0,
+    })
+}
+
+/// Get the number of replies to the comments of the user an auth token belongs to,
+/// posted strictly after a given date. Dates come from the `title` of the ages
+/// HackerNews displays, so they are precise to the second.
+///
+/// Only the replies displayed on the first page of the user’s threads are counted.
+/// Will return an [`HnError::Unauthenticated`] error if the token is invalid or expired.
+#[cfg(feature = "fetch")]
+pub async fn replies_since(token: &str, since: &DateTime<Utc>) -> Result<u32, HnError> {
+    let (username, comments) = my_threads(token).await?;
+    Ok(count_replies(&comments, &username, since))
+}
+
+/// Username of the user an auth token belongs to, and the comment tree of the first
+/// page of their threads.
+#[cfg(feature = "fetch")]
+async fn my_threads(token: &str) -> Result<(String, Vec<Rc<Comment>>), HnError> {
+    let username = current_user(token).await?.ok_or(HnError::Unauthenticated)?;
+    let url = format!("{}/threads?id={}", BASE_URL, username);
+    let document = document_at_url(&url, &Some(token.to_string())).await?;
+    let mut comments = vec![];
+    extend_comment_tree(&document, &mut comments, &mut vec![], &mut 0, None);
+    Ok((username, comments))
+}
+
+/// Get the settings of the user an auth token belongs to, from the form of their
+/// profile page. Unlike [`user_details`], it includes private settings such as the
+/// email address. Will return an [`HnError::Unauthenticated`] error if the token is
+/// invalid or expired.
+#[cfg(feature = "fetch")]
+pub async fn my_profile(token: &str) -> Result<MyProfile, HnError> {
+    let username = current_user(token).await?.ok_or(HnError::Unauthenticated)?;
+    let url = format!("{}/user?id={}", BASE_URL, username);
+    let html = html_at_url(&url, &Some(token.to_string())).await?;
+    let document = Html::parse_document(&html);
+    profile_form_fields(&document)
+        .and_then(|fields| my_profile_from_fields(&fields))
+        .ok_or(HnError::Unauthenticated)
+}
+
+/// Fields of the form to edit a profile, as they would be submitted. HackerNews
+/// displays it only to the user themselves.
+fn profile_form_fields(document: &Html) -> Option<Vec<(String, String)>> {
+    form_fields(document, "textarea[name='about']")
+}
+
+/// Fields of the form containing an element matching `selector`, as they would be
+/// submitted (without the submit buttons).
+fn form_fields(document: &Html, selector: &str) -> Option<Vec<(String, String)>> {
+    let form = document
+        .select(&Selector::parse("form").unwrap())
+        .find(|form| single_element(form, selector).is_some())?;
+    let option_selector = Selector::parse("option").unwrap();
+    let mut fields = vec![];
+    for el in form.select(&Selector::parse("input[name], textarea[name], select[name]").unwrap()) {
+        let value = match el.value().name() {
+            "textarea" => el.text().collect(),
+            "select" => {
+                let options: Vec<ElementRef> = el.select(&option_selector).collect();
+                options
+                    .iter()
+                    .find(|option| option.value().attr("selected").is_some())
+                    .or_else(|| options.first())
+                    .map(|option| match option.value().attr("value") {
+                        Some(value) => value.to_string(),
+                        None => option.text().collect(),
+                    })
+                    .unwrap_or_default()
+            }
+            _ if el.value().attr("type") == Some("submit") => continue,
+            _ => el.value().attr("value").unwrap_or_default().to_string(),
+        };
+        fields.push((el.value().attr("name").unwrap().to_string(), value));
+    }
+    Some(fields)
+}
+
+/// Replace the about text (biography) of the user an auth token belongs to. The text is
+/// the one typed in the form of the profile page, using HackerNews formatting (not
+/// HTML). The other settings are left as they are.
+///
+/// Will return an [`HnError::Unauthenticated`] error if the token is invalid or
+/// expired, and an [`HnError::Rejected`] error if HackerNews doesn’t accept the update.
+#[cfg(feature = "fetch")]
+pub async fn update_about(text: &str, token: &str) -> Result<(), HnError> {
+    let username = current_user(token).await?.ok_or(HnError::Unauthenticated)?;
+    let url = format!("{}/user?id={}", BASE_URL, username);
+    let html = html_at_url(&url, &Some(token.to_string())).await?;
+    let fields =
+        profile_form_fields(&Html::parse_document(&html)).ok_or(HnError::Unauthenticated)?;
+    submit_form("xuser", &fields, ("about", text), token).await
+}
+
+/// Replace the text of a comment posted by the user an auth token belongs to. The text
+/// uses HackerNews formatting (not HTML). HackerNews only allows editing a comment for a
+/// while after posting it, as long as it displays its [`Comment::edit_url`].
+///
+/// Will return an [`HnError::Unauthenticated`] error if the token is invalid or
+/// expired, and an [`HnError::Rejected`] error if HackerNews doesn’t let the user edit
+/// the comment, e.g. because the edit window has expired.
+#[cfg(feature = "fetch")]
+pub async fn edit_comment(id: u32, new_text: &str, token: &str) -> Result<(), HnError> {
+    let url = format!("{}/edit?id={}", BASE_URL, id);
+    let html = html_at_url(&url, &Some(token.to_string())).await?;
+    let fields = form_fields(&Html::parse_document(&html), "textarea[name='text']")
+        .ok_or_else(|| form_refusal(&html, "The comment can no longer be edited."))?;
+    submit_form("xedit", &fields, ("text", new_text), token).await
+}
+
+/// Delete a comment posted by the user an auth token belongs to, confirming it as on
+/// the page of its [`Comment::delete_url`]. HackerNews only allows deleting a comment
+/// for a while after posting it, and as long as it has no replies.
+///
+/// Will return an [`HnError::Unauthenticated`] error if the token is invalid or
+/// expired, and an [`HnError::Rejected`] error if HackerNews doesn’t let the user delete
+/// the comment, e.g. because it has replies or the delete window has expired.
+#[cfg(feature = "fetch")]
+pub async fn delete_comment(id: u32, token: &str) -> Result<(), HnError> {
+    let url = format!("{}/delete-confirm?id={}", BASE_URL, id);
+    let html = html_at_url(&url, &Some(token.to_string())).await?;
+    let fields = form_fields(&Html::parse_document(&html), "input[name='d']").ok_or_else(|| {
+        form_refusal(
+            &html,
+            "The comment can no longer be deleted, e.g. because it has replies.",
+        )
+    })?;
+    // The confirmation form has a “Yes” and a “No” button, both named `d`.
+    submit_form("xdelete", &fields, ("d", "Yes"), token).await
+}
+
+/// Error for a page of HackerNews missing the expected form: it displays the login form
+/// when the token is invalid or expired, and a message otherwise (`default_message` if
+/// it is empty).
+#[cfg(feature = "fetch")]
+fn form_refusal(html: &str, default_message: &str) -> HnError {
+    if single_doc_element(&Html::parse_document(html), "input[name='acct']").is_some() {
+        return HnError::Unauthenticated;
+    }
+    let message = rejection_message(html);
+    HnError::Rejected(if message.is_empty() {
+        default_message.to_string()
+    } else {
+        message
+    })
+}
+
+/// Submit a form of HackerNews to `path`, with the given fields, `field` giving the
+/// name and value of a field to set (replacing the one of the form, if any). HackerNews
+/// redirects once the form is accepted, and otherwise displays a message, returned in
+/// an [`HnError::Rejected`] error.
+#[cfg(feature = "fetch")]
+async fn submit_form(
+    path: &str,
+    fields: &[(String, String)],
+    field: (&str, &str),
+    token: &str,
+) -> Result<(), HnError> {
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(
+            fields
+                .iter()
+                .filter(|(name, _)| name != field.0)
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+        .append_pair(field.0, field.1)
+        .finish();
+
+    let client = client_builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let url = format!("{}/{}", BASE_URL, path);
+    debug!("POST {}", url);
+    let start = Instant::now();
+    let response = client
+        .post(&url)
+        .header(COOKIE, cookie_header(token))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .inspect_err(|err| warn!("POST {} failed: {}", url, err))?;
+    debug!(
+        "POST {} -> {} in {:?}",
+        url,
+        response.status(),
+        start.elapsed()
+    );
+    if response.status().is_redirection() {
+        return Ok(());
+    }
+    let html = response.text().await?;
+    Err(HnError::Rejected(rejection_message(&html)))
+}
+
+/// Text of the page HackerNews displays when it refuses a form.
+#[cfg(feature = "fetch")]
+fn rejection_message(html: &str) -> String {
+    let document = Html::parse_document(html);
+    let text = document.root_element().text().collect::<String>();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn my_profile_from_fields(fields: &[(String, String)]) -> Option<MyProfile> {
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(field_name, _)| field_name == name)
+            .map(|(_, value)| value.trim())
+    };
+    Some(MyProfile {
+        id: field("id")?.to_string(),
+        about: field("about").unwrap_or_default().to_string(),
+        email: field("uemail")
+            .filter(|email| !email.is_empty())
+            .map(ToString::to_string),
+        show_dead: field("showd") == Some("yes"),
+        no_procrast: field("nopro") == Some("yes"),
+        max_visit: field("maxv").and_then(|value| value.parse().ok()),
+        min_away: field("minaw").and_then(|value| value.parse().ok()),
+        delay: field("delay").and_then(|value| value.parse().ok()),
+    })
+}
+
+/// Get the stories previously posted with a given URL, most relevant first, to know
+/// whether it has already been discussed on HackerNews.
+///
+/// HackerNews pages don’t list them, so they are obtained from the
+/// [HackerNews search API](https://hn.algolia.com/api) (by Algolia). The URLs are
+/// compared regardless of their scheme, a `www.` prefix and a trailing slash. Since the
+/// search API doesn’t provide them, the stories have no `upvote_auth`, and their
+/// `date_displayed` is obtained with [`humanize`].
+///
+/// ## Example
+///
+/// ```
+/// use hnapi::past_discussions;
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let stories = past_discussions("https://www.rust-lang.org/").await?;
+///     assert!(!stories.is_empty());
+///     Ok(())
+/// }
+/// ```
+#[cfg(feature = "fetch")]
+pub async fn past_discussions(url: &str) -> Result<Vec<Story>, HnError> {
+    let client = client_builder().build()?;
+    debug!("GET {} for {}", SEARCH_URL, url);
+    let start = Instant::now();
+    let resp = client
+        .get(SEARCH_URL)
+        .query(&[
+            ("query", url),
+            ("restrictSearchableAttributes", "url"),
+            ("tags", "story"),
+        ])
+        .send()
+        .await
+        .inspect_err(|err| warn!("GET {} failed: {}", SEARCH_URL, err))?;
+    debug!(
+        "GET {} -> {} in {:?}",
+        SEARCH_URL,
+        resp.status(),
+        start.elapsed()
+    );
+    let search: SearchResponse = resp.json().await?;
+    Ok(search
+        .hits
+        .into_iter()
+        .filter(|hit| {
+            hit.url
+                .as_deref()
+                .is_some_and(|hit_url| same_url(hit_url, url))
+        })
+        .filter_map(story_from_search_hit)
+        .collect())
+}
+
+/// Response of the HackerNews search API, with only the fields we need.
+#[cfg(feature = "fetch")]
+#[derive(Deserialize)]
+struct SearchResponse {
+    hits: Vec<SearchHit>,
+}
+
+#[cfg(feature = "fetch")]
+#[derive(Deserialize)]
+struct SearchHit {
+    #[serde(rename = "objectID")]
+    object_id: String,
+    title: Option<String>,
+    url: Option<String>,
+    author: Option<String>,
+    points: Option<u32>,
+    num_comments: Option<u32>,
+    created_at_i: i64,
+}
+
+#[cfg(feature = "fetch")]
+fn story_from_search_hit(hit: SearchHit) -> Option<Story> {
+    let url = Url::from_str(hit.url.as_deref()?).ok()?;
+    let date = Utc.timestamp(hit.created_at_i, 0);
+    Some(Story {
+        id: hit.object_id.parse().ok()?,
+        title: hit.title?,
+        url_displayed: url
+            .host_str()
+            .map(|host| host.trim_start_matches("www.").to_string()),
+        url,
+        upvote_auth: None,
+        user: hit.author,
+        score: hit.points,
+        date,
+        date_displayed: humanize(date),
+        comment_count: hit.num_comments,
+        kind: StoryKind::Regular,
+    })
+}
+
+/// Whether two URLs point to the same page, ignoring differences that people
+/// submitting the same link often have.
+#[cfg(feature = "fetch")]
+fn same_url(a: &str, b: &str) -> bool {
+    fn normalize(url: &str) -> &str {
+        let url = url
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        url.trim_start_matches("www.").trim_end_matches('/')
+    }
+    normalize(a) == normalize(b)
+}
+
+#[cfg(feature = "fetch")]
+async fn document_at_url(url: &str, token: &Option<String>) -> Result<Html, HnError> {
+    let html = html_at_url(url, token).await?;
+    Ok(Html::parse_document(&html))
+}
+
+/// HTML of a page of HackerNews. Will return an [`HnError::RateLimited`] error if
+/// HackerNews answers with its page asking to slow down, instead of the requested one,
+/// and an [`HnError::Status`] error if it answers with another error status.
+#[cfg(feature = "fetch")]
+async fn html_at_url(url: &str, token: &Option<String>) -> Result<String, HnError> {
+    let client = client_builder().build()?;
+    let mut request_builder = client.get(url);
+    if let Some(token) = token {
+        request_builder = request_builder.header(COOKIE, cookie_header(token));
+    }
+    debug!("GET {}", url);
+    let start = Instant::now();
+    let resp = request_builder
+        .send()
+        .await
+        .inspect_err(|err| warn!("GET {} failed: {}", url, err))?;
+    debug!("GET {} -> {} in {:?}", url, resp.status(), start.elapsed());
+    if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        warn!("GET {} was rate limited", url);
+        return Err(HnError::RateLimited);
+    }
+    if resp.status().is_client_error() || resp.status().is_server_error() {
+        warn!("GET {} failed with status {}", url, resp.status());
+        return Err(HnError::Status(resp.status().as_u16()));
+    }
+    let html = resp.text().await?;
+    if is_rate_limited(&html) {
+        warn!("GET {} was rate limited", url);
+        return Err(HnError::RateLimited);
+    }
+    Ok(html)
+}
+
+/// Whether a page is the one HackerNews displays to clients making too many requests.
+#[cfg(feature = "fetch")]
+fn is_rate_limited(html: &str) -> bool {
+    html.len() < 1000 && html.contains("not able to serve your requests this quickly")
+}
+
+/// Login and get a session, whose [`Auth::token`] is used by the functions requiring
+/// authentication. Will return `None` if the credentials are invalid, and an
+/// [`HnError::Rejected`] error if HackerNews didn’t let the user log in for another
+/// reason, e.g. because it requires solving a captcha.
+#[cfg(feature = "fetch")]
+pub async fn login(username: &str, password: &str) -> Result<Option<Auth>, HnError> {
+    let client = client_builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let url = format!("{}/login", BASE_URL);
+    let body = url::form_urlencoded::Serializer::new(String::new())
+        .append_pair("goto", "news")
+        .append_pair("acct", username)
+        .append_pair("pw", password)
+        .finish();
+    debug!("POST {}", url);
+    let start = Instant::now();
+    let response = client
+        .post(&url)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(body)
+        .send()
+        .await
+        .inspect_err(|err| warn!("POST {} failed: {}", url, err))?;
+    debug!(
+        "POST {} -> {} in {:?}",
+        url,
+        response.status(),
+        start.elapsed()
+    );
+    let cookies = response
+        .cookies()
+        .map(|cookie| LoginCookie {
+            name: cookie.name().to_string(),
+            value: cookie.value().to_string(),
+            expires: cookie.expires().map(DateTime::<Utc>::from),
+        })
+        .collect();
+    let auth = match auth_from_cookies(cookies) {
+        Some(auth) => auth,
+        // Without a session, HackerNews displays a page telling why instead of
+        // redirecting.
+        None => {
+            let html = response.text().await?;
+            return match login_failure(&html) {
+                Some(err) => Err(err),
+                None => Ok(None),
+            };
+        }
+    };
+    // The session is confirmed on the page HackerNews redirects to, where the user is
+    // displayed once logged in.
+    if current_user(&auth.token()).await?.is_none() {
+        warn!("Session cookie set on login, but not logged in with it");
+        return Err(HnError::Rejected(
+            "HackerNews didn’t keep the session after logging in.".to_string(),
+        ));
+    }
+    Ok(Some(auth))
+}
+
+/// Reason why a login didn’t start a session, from the page HackerNews displayed
+/// instead. Will return `None` if the credentials are invalid.
+#[cfg(feature = "fetch")]
+fn login_failure(html: &str) -> Option<HnError> {
+    let message = rejection_message(html);
+    if message.starts_with("Bad login") {
+        None
+    } else if html.contains("g-recaptcha") || message.contains("Validation required") {
+        Some(HnError::Rejected(
+            "HackerNews requires solving a captcha to log in. Log in on the website once, then try again."
+                .to_string(),
+        ))
+    } else {
+        Some(HnError::Rejected(message))
+    }
+}
+
+/// Cookie set by HackerNews in the login response.
+#[cfg(feature = "fetch")]
+struct LoginCookie {
+    name: String,
+    value: String,
+    expires: Option<DateTime<Utc>>,
+}
+
+/// Session from the cookies set on login. The login succeeded only if the `user`
+/// cookie is among them, wherever it is.
+#[cfg(feature = "fetch")]
+fn auth_from_cookies(cookies: Vec<LoginCookie>) -> Option<Auth> {
+    let expires = cookies.iter().find(|cookie| cookie.name == "user")?.expires;
+    let cookies = cookies
+        .into_iter()
+        .map(|cookie| (cookie.name, cookie.value))
+        .collect();
+    Some(Auth { cookies, expires })
+}
+
+#[cfg(feature = "fetch")]
+pub async fn upvote_story(id: u32, upvote_auth: &str, token: &str) -> Result<bool, HnError> {
+    let url = format!(
+        "{}/vote?id={}&how=up&auth={}&goto=news",
+        BASE_URL, id, upvote_auth
+    );
+    let document = document_at_url(&url, &Some(token.to_string())).await?;
+    if single_doc_element(&document, "form[action='vote']").is_some() {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+/// Story from its row (`tr.athing`) and the next one, with its score, user and date. Will
+/// return `None` if the rows don't have the expected markup, or if the date can’t be
+/// parsed.
+fn extract_story_info(first_line_el: &ElementRef) -> Option<Story> {
+    let id = first_line_el.value().attr("id")?.parse().ok()?;
+    let title_el = single_element(first_line_el, ".titlelink")?;
+    let (title, url) = link_info(&title_el);
+    let url_displayed = single_element_html(first_line_el, ".sitestr");
+    let upvote_auth = single_element(first_line_el, ".clicky").and_then(|upvote_link| {
+        let (_, upvote_url) = link_info(&upvote_link);
+        upvote_url.query_pairs().find_map(|(key, value)| {
+            if key == "auth" {
+                Some(value.to_string())
+            } else {
+                None
+            }
+        })
+    });
+
+    // The rows are usually separated by whitespace, i.e. text nodes.
+    let second_line_el = match first_line_el.next_siblings().find_map(ElementRef::wrap) {
+        Some(el) => el,
+        None => {
+            warn!("No subtext row found for story {}", id);
+            return None;
+        }
+    };
+    let score =
+        single_element_html(&second_line_el, ".score").and_then(|score| parse_score(&score));
+    let user = single_element_html(&second_line_el, ".hnuser");
+    let (date, date_displayed) = match parse_age(&second_line_el) {
+        Some(age) => age,
+        None => {
+            warn!("No date found for story {}", id);
+            return None;
+        }
+    };
+
+    // Stories without comments have a “discuss” link instead of “N comments”.
     let comment_count = second_line_el
         .select(&Selector::parse("a").unwrap())
-        .find(|el| el.inner_html().contains("&nbsp;comment"))
-        .map(|el| parse_comment_count(el.inner_html()));
+        .find_map(|el| {
+            let html = el.inner_html();
+            if html.contains("&nbsp;comment") {
+                parse_comment_count(&html)
+            } else if html.trim() == "discuss" {
+                Some(0)
+            } else {
+                None
+            }
+        });
+
+    // Job offers have only a date under their title.
+    let kind = if score.is_none() && user.is_none() {
+        StoryKind::Job
+    } else {
+        StoryKind::Regular
+    };
 
-    Story {
+    Some(Story {
         id,
         title,
         url,
@@ -396,56 +2130,292 @@ fn extract_story_info(first_line_el: &ElementRef) -> Story {
         date,
         date_displayed,
         comment_count,
+        kind,
+    })
+}
+
+/// Comment from its row (`tr.comtr`), without its replies. Will return `None` if the row
+/// has no id, or if the date can’t be parsed.
+fn extract_comment_info(comment_el: &ElementRef) -> Option<Comment> {
+    let id: u32 = comment_el.value().attr("id")?.parse().ok()?;
+
+    let user = single_element_html(comment_el, ".hnuser");
+    let togg = single_element(comment_el, "a.togg");
+    let thread_size = togg
+        .and_then(|togg| togg.value().attr("n"))
+        .and_then(|n| n.parse().ok());
+    // Collapsed comments have the `coll` class, and their toggle reads “[+]” or
+    // “[3 more]” instead of “[–]”.
+    let collapsed = comment_el.value().classes().any(|class| class == "coll")
+        || togg.is_some_and(|togg| {
+            let text = togg.inner_html();
+            text.contains('+') || text.contains("more")
+        });
+    let (date, date_displayed) = match parse_age(comment_el) {
+        Some(age) => age,
+        None => {
+            warn!("No date found for comment {}", id);
+            return None;
+        }
+    };
+
+    let html_content = single_element(comment_el, ".commtext").map(|el| {
+        let first_paragraph = el.text().next().unwrap_or("");
+        let other_paragraphes = el
+            .children()
+            .flat_map(ElementRef::wrap)
+            .filter(|el| el.value().attr("class") != Some("reply"))
+            .map(|el| el.html())
+            .collect::<Vec<_>>()
+            .join("");
+        format!("{}{}", first_paragraph, other_paragraphes)
+    });
+
+    let author_link = |prefix: &str| {
+        comment_el
+            .select(&Selector::parse(".comhead a").unwrap())
+            .find(|a| {
+                a.value()
+                    .attr("href")
+                    .is_some_and(|href| href.starts_with(prefix))
+            })
+            .map(|a| link_info(&a).1)
+    };
+    let edit_url = author_link("edit?");
+    let delete_url = author_link("delete-confirm?");
+
+    let placeholder = html_content
+        .as_deref()
+        .map(str::trim)
+        .filter(|html| is_placeholder(html))
+        .map(ToString::to_string);
+    let deleted = user.is_none() || html_content.is_none() || placeholder.is_some();
+    if deleted {
+        debug!("Comment {} is a placeholder for a deleted comment", id);
+    }
+
+    Some(Comment {
+        id,
+        user: if deleted {
+            "".to_string()
+        } else {
+            user.unwrap_or_default()
+        },
+        date,
+        date_displayed,
+        html_content: if deleted {
+            placeholder.unwrap_or_else(|| "[deleted]".to_string())
+        } else {
+            html_content.unwrap_or_default()
+        },
+        deleted,
+        collapsed,
+        edit_url,
+        delete_url,
+        parent: RefCell::new(None),
+        children: RefCell::new(vec![]),
+        thread_size,
+    })
+}
+
+/// Whether a comment text is one of the placeholders HN displays instead of the
+/// content of deleted, flagged or dead comments (e.g. “[flagged] [dead]”).
+fn is_placeholder(text: &str) -> bool {
+    Regex::new(r"^(\[(deleted|flagged|dead)\]\s*)+$")
+        .unwrap()
+        .is_match(text)
+}
+
+fn story_page_from_document(document: &Html, page: usize) -> StoryPage {
+    let stories: HashMap<usize, Story> = document
+        .select(&Selector::parse("tr.athing").unwrap())
+        .enumerate()
+        .filter_map(|(index, tr)| {
+            // Job offers are not ranked on the jobs page, so their position is used.
+            let rank = single_element_html(&tr, ".rank")
+                .and_then(|rank| rank.trim().trim_end_matches('.').parse::<usize>().ok())
+                .unwrap_or_else(|| page.saturating_sub(1) * STORIES_PER_PAGE + index + 1);
+            let story = extract_story_info(&tr)?;
+            Some((rank, story))
+        })
+        .collect();
+    let more_token = more_token(document);
+    StoryPage {
+        stories,
+        page,
+        has_more: more_token.is_some(),
+        more_token,
+    }
+}
+
+/// Number of stories on a page of a list.
+const STORIES_PER_PAGE: usize = 30;
+
+/// Relative URL of the “More” link at the bottom of a page, if any.
+fn more_token(document: &Html) -> Option<String> {
+    single_doc_element(document, "a.morelink")
+        .and_then(|a| a.value().attr("href"))
+        .map(ToString::to_string)
+}
+
+/// Text of the story on its page, if any. Recent pages have it in a `.toptext` element;
+/// older ones in the last cell of a row of the `fatitem` table, after the rows with the
+/// title and the subtext, and before the one with the comment form (only present when
+/// logged in). Rows are selected by their content rather than their position, which
+/// depends on whitespace and spacer rows.
+fn story_text(document: &Html) -> Option<String> {
+    if let Some(toptext) = single_doc_element(document, "table.fatitem .toptext") {
+        let html = toptext.inner_html().trim().to_string();
+        return Some(html).filter(|html| !html.is_empty());
+    }
+    let form_selector = Selector::parse("form").unwrap();
+    document
+        .select(
+            &Selector::parse("table.fatitem > tbody > tr:not(.athing) > td:last-child").unwrap(),
+        )
+        .filter(|td| td.value().attr("class") != Some("subtext"))
+        .filter(|td| td.select(&form_selector).next().is_none())
+        .map(|td| td.inner_html().trim().to_string())
+        .find(|html| !html.is_empty())
+}
+
+/// Number of comments on a page of a discussion, and URL of the next page if any.
+fn comments_page_info(html: &str) -> (usize, Option<Url>) {
+    let document = Html::parse_document(html);
+    let comment_count = document
+        .select(&Selector::parse(".comment-tree tr.comtr").unwrap())
+        .count();
+    let more_url = single_doc_element(&document, "a.morelink").map(|a| link_info(&a).1);
+    (comment_count, more_url)
+}
+
+/// Add the comments of a discussion page to the tree, `parent_stack` containing the
+/// last comment seen at each level. Comments are added until `comment_count` reaches
+/// `max_comments`; returns `true` if some comments had to be left out.
+fn extend_comment_tree(
+    document: &Html,
+    comments: &mut Vec<Rc<Comment>>,
+    parent_stack: &mut Vec<Rc<Comment>>,
+    comment_count: &mut usize,
+    max_comments: Option<usize>,
+) -> bool {
+    let comments_selector = Selector::parse(".comment-tree tr.comtr").unwrap();
+    let ind_selector = Selector::parse(".ind").unwrap();
+    for comment_tr in document.select(&comments_selector) {
+        if max_comments.is_some_and(|max| *comment_count >= max) {
+            return true;
+        }
+        let indent = comment_tr
+            .select(&ind_selector)
+            .next()
+            .and_then(|ind| ind.value().attr("indent"))
+            .map(|ind| ind.parse::<usize>().unwrap())
+            .unwrap_or_else(|| {
+                warn!("No .ind element found for a comment, assuming a top-level comment");
+                0
+            });
+        // The replies of a skipped comment go to the comment it answers.
+        let comment = match extract_comment_info(&comment_tr) {
+            Some(comment) => Rc::new(comment),
+            None => continue,
+        };
+        *comment_count += 1;
+
+        while indent < parent_stack.len() {
+            parent_stack.pop();
+        }
+
+        if let Some(parent) = parent_stack.last().filter(|_| indent > 0) {
+            (*parent.children.borrow_mut()).push(Rc::clone(&comment));
+            (*comment.parent.borrow_mut()) = Some(Rc::downgrade(parent));
+        } else {
+            comments.push(Rc::clone(&comment));
+        }
+        parent_stack.push(comment);
     }
+    false
+}
+
+/// Number of replies to the comments posted by a user strictly after a given date, in
+/// a comment tree.
+#[cfg(feature = "fetch")]
+fn count_replies(comments: &[Rc<Comment>], username: &str, since: &DateTime<Utc>) -> u32 {
+    comments
+        .iter()
+        .map(|comment| {
+            let children = comment.children.borrow();
+            let replies = if comment.user == username {
+                children
+                    .iter()
+                    .filter(|child| !child.deleted && child.user != username && child.date > *since)
+                    .count() as u32
+            } else {
+                0
+            };
+            replies + count_replies(&children, username, since)
+        })
+        .sum()
 }
 
-fn extract_comment_info(comment_el: &ElementRef) -> Comment {
-    let id = comment_el.value().attr("id").unwrap().parse().unwrap();
+/// Dates of the comments posted by a user in a comment tree. The threads page also
+/// contains the replies from other users, which are ignored.
+#[cfg(feature = "fetch")]
+fn collect_comment_dates(comments: &[Rc<Comment>], username: &str, dates: &mut Vec<DateTime<Utc>>) {
+    for comment in comments {
+        if comment.user == username {
+            dates.push(comment.date);
+        }
+        collect_comment_dates(&comment.children.borrow(), username, dates);
+    }
+}
 
-    let user = single_element_html(comment_el, ".hnuser").unwrap();
-    let (date, date_displayed) = single_element(comment_el, ".age")
-        .map(|d| date_info(&d))
-        .unwrap();
+#[cfg(feature = "fetch")]
+fn activity_by_day(dates: &[DateTime<Utc>]) -> Vec<(NaiveDate, u32)> {
+    let mut counts: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+    for date in dates {
+        *counts.entry(date.naive_utc().date()).or_insert(0) += 1;
+    }
+    counts.into_iter().collect()
+}
 
-    let html_content = single_element(comment_el, ".commtext")
-        .map(|el| {
-            let first_paragraph = el.text().next().unwrap_or("");
-            let other_paragraphes = el
-                .children()
-                .flat_map(ElementRef::wrap)
-                .filter(|el| el.value().attr("class") != Some("reply"))
-                .map(|el| el.html())
-                .collect::<Vec<_>>()
-                .join("");
-            format!("{}{}", first_paragraph, other_paragraphes)
+/// Text of an element, where links are replaced with their URL.
+fn plain_text(el: &ElementRef) -> String {
+    if el.value().name() == "a" {
+        if let Some(href) = el.value().attr("href") {
+            return href.to_string();
+        }
+    }
+    el.children()
+        .map(|child| {
+            if let Some(text) = child.value().as_text() {
+                text.to_string()
+            } else if let Some(child_el) = ElementRef::wrap(child) {
+                plain_text(&child_el)
+            } else {
+                "".to_string()
+            }
         })
-        .unwrap();
+        .collect()
+}
 
-    Comment {
-        id,
-        user,
-        date,
-        date_displayed,
-        html_content,
-        parent: RefCell::new(None),
-        children: RefCell::new(vec![]),
+fn parse_score(score: &str) -> Option<u32> {
+    let parsed = parse_number(score);
+    if parsed.is_none() {
+        warn!("Unable to parse score “{}”", score);
     }
+    parsed
 }
 
-fn parse_score(score: String) -> u32 {
-    score
-        .replace(" points", "")
-        .replace(" point", "")
-        .parse()
-        .unwrap()
+/// Parse a comment count such as “42&nbsp;comments”.
+fn parse_comment_count(comment_count: &str) -> Option<u32> {
+    parse_number(comment_count)
 }
 
-fn parse_comment_count(comment_count: String) -> u32 {
-    comment_count
-        .replace("&nbsp;comments", "")
-        .replace("&nbsp;comment", "")
-        .parse()
-        .unwrap()
+/// Parse the number in a text such as “1,234 points”, ignoring anything that is not a
+/// digit (e.g. separators) instead of failing.
+fn parse_number(text: &str) -> Option<u32> {
+    let digits: String = text.chars().filter(char::is_ascii_digit).collect();
+    digits.parse().ok()
 }
 
 fn single_doc_element<'a>(document: &'a Html, selector: &str) -> Option<ElementRef<'a>> {
@@ -471,21 +2441,99 @@ fn link_info(link_el: &ElementRef) -> (String, Url) {
     (inner_html, url)
 }
 
-fn date_info(date_el: &ElementRef) -> (DateTime<Utc>, String) {
-    let date =
-        DateTime::from_str(&format!("{}.000Z", date_el.value().attr("title").unwrap())).unwrap();
-    let date_displayed = ElementRef::wrap(date_el.first_child().unwrap())
+/// Date of a story or comment, and the text HackerNews displays for it, from the `.age`
+/// element in `el`. The date is the one of the `title` attribute of the element, or
+/// the one of the displayed text (e.g. “2 hours ago”) if the attribute is missing or
+/// invalid. Without displayed text, the date is humanized. Will return `None` if there
+/// is no `.age` element or no date can be read from it.
+fn parse_age(el: &ElementRef) -> Option<(DateTime<Utc>, String)> {
+    let age_el = single_element(el, ".age")?;
+    let displayed = age_el
+        .first_child()
+        .and_then(ElementRef::wrap)
+        .map(|el| el.inner_html())
+        .filter(|displayed| !displayed.trim().is_empty());
+    let date = age_el
+        .value()
+        .attr("title")
+        .and_then(parse_age_title)
+        .or_else(|| parse_relative_age(displayed.as_deref()?, Utc::now()))?;
+    let displayed = displayed.unwrap_or_else(|| humanize(date));
+    Some((date, displayed))
+}
+
+/// Date of the `title` attribute of an `.age` element, e.g. “2021-11-17T10:00:00”,
+/// possibly followed by the Unix timestamp of the same date.
+fn parse_age_title(title: &str) -> Option<DateTime<Utc>> {
+    let date = title.split_whitespace().next()?;
+    DateTime::from_str(&format!("{}.000Z", date)).ok()
+}
+
+/// Date from an age as HackerNews displays it, relative to `now`, e.g. “5 minutes ago”.
+/// Months and years are approximated to 30 and 365 days.
+fn parse_relative_age(text: &str, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if text == "just now" {
+        return Some(now);
+    }
+    let caps = Regex::new(r"^(\d+) (minute|hour|day|month|year)s? ago$")
         .unwrap()
-        .inner_html();
-    (date, date_displayed)
+        .captures(text)?;
+    let count = i64::from(caps[1].parse::<u16>().ok()?);
+    let elapsed = match &caps[2] {
+        "minute" => Duration::minutes(count),
+        "hour" => Duration::hours(count),
+        "day" => Duration::days(count),
+        "month" => Duration::days(30 * count),
+        _ => Duration::days(365 * count),
+    };
+    Some(now - elapsed)
+}
+
+/// Date as HackerNews displays it, relative to now: “5 minutes ago”, “1 day ago”,
+/// “3 months ago” or “2 years ago”, months and years being 30 and 365 days. It is what
+/// `date_displayed` would contain, e.g. to show dates that don’t come from HackerNews
+/// the same way.
+pub fn humanize(date: DateTime<Utc>) -> String {
+    humanize_at(date, Utc::now())
+}
+
+fn humanize_at(date: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now - date;
+    let ago = |count: i64, unit: &str| {
+        format!(
+            "{} {}{} ago",
+            count,
+            unit,
+            if count == 1 { "" } else { "s" }
+        )
+    };
+    if elapsed < Duration::minutes(1) {
+        "just now".to_string()
+    } else if elapsed < Duration::hours(1) {
+        ago(elapsed.num_minutes(), "minute")
+    } else if elapsed < Duration::days(1) {
+        ago(elapsed.num_hours(), "hour")
+    } else if elapsed < Duration::days(30) {
+        ago(elapsed.num_days(), "day")
+    } else if elapsed < Duration::days(365) {
+        ago(elapsed.num_days() / 30, "month")
+    } else {
+        ago(elapsed.num_days() / 365, "year")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(not(feature = "fetch"))]
+    use chrono::TimeZone;
+    #[cfg(feature = "fetch")]
     use serial_test::serial;
+    #[cfg(feature = "fetch")]
     use std::error::Error;
 
+    #[cfg(feature = "fetch")]
     #[tokio::test]
     #[serial]
     async fn top_stories_return_something() -> Result<(), Box<dyn Error>> {
@@ -494,16 +2542,18 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "fetch")]
     #[tokio::test]
     #[serial]
     async fn story_details_return_something() -> Result<(), Box<dyn Error>> {
         let details = story_details(27883047).await?.unwrap();
 
         let comment = details.comments.get(1).unwrap();
-        let children = comment.children.borrow();
-        let child = (*children).get(0).unwrap();
-        let child_parent = child.parent.borrow().as_ref().unwrap().upgrade().unwrap();
+        let children = comment.children();
+        let child = children.first().unwrap();
+        let child_parent = child.parent().unwrap();
         assert_eq!(child_parent.id, comment.id);
+        assert!(comment.parent().is_none());
 
         assert_eq!(details.story.id, 27883047);
         assert_eq!(
@@ -528,6 +2578,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "fetch")]
     #[tokio::test]
     #[serial]
     async fn story_details_return_something_with_text() -> Result<(), Box<dyn Error>> {
@@ -536,6 +2587,41 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "fetch")]
+    #[tokio::test]
+    #[serial]
+    async fn stories_details_keep_the_order_of_ids() -> Result<(), Box<dyn Error>> {
+        let stories = stories_details(&[29246573, u32::MAX, 27883047]).await?;
+        let ids: Vec<Option<u32>> = stories
+            .iter()
+            .map(|details| details.as_ref().map(|details| details.story.id))
+            .collect();
+        assert_eq!(ids, vec![Some(29246573), None, Some(27883047)]);
+        Ok(())
+    }
+
+    #[cfg(feature = "fetch")]
+    #[tokio::test]
+    #[serial]
+    async fn large_compressed_discussions_are_parsed() -> Result<(), Box<dyn Error>> {
+        let details = story_details(29432276).await?.unwrap();
+        assert_eq!(details.story.id, 29432276);
+        // The discussion has hundreds of comments, spread on several pages.
+        let comment_count: usize = details.comments.iter().map(|c| thread_size(c)).sum();
+        assert!(comment_count > 200);
+        Ok(())
+    }
+
+    #[cfg(feature = "fetch")]
+    fn thread_size(comment: &Comment) -> usize {
+        1 + comment
+            .children()
+            .iter()
+            .map(|child| thread_size(child))
+            .sum::<usize>()
+    }
+
+    #[cfg(feature = "fetch")]
     #[tokio::test]
     #[serial]
     async fn user_details_returns_none_for_nonexistent_id() -> Result<(), Box<dyn Error>> {
@@ -544,6 +2630,7 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "fetch")]
     #[tokio::test]
     #[serial]
     async fn user_details_returns_details_for_existent_id() -> Result<(), Box<dyn Error>> {
@@ -556,4 +2643,885 @@ mod tests {
         assert!(!user.about.is_empty());
         Ok(())
     }
+
+    #[test]
+    fn items_without_a_date_are_skipped() {
+        let document = Html::parse_document(
+            r#"<table><tr class="athing" id="1"><td class="title"><a href="https://example.com/" class="titlelink">Story</a></td></tr>
+            <tr><td class="subtext"><span class="score">1 point</span> by <a href="user?id=someone" class="hnuser">someone</a> <span class="age"><a href="item?id=1">a while ago</a></span></td></tr></table>"#,
+        );
+        let tr = single_doc_element(&document, "tr.athing").unwrap();
+        assert!(extract_story_info(&tr).is_none());
+
+        let document = Html::parse_document(
+            r#"<table class="comment-tree">
+              <tr class="athing comtr" id="2"><td><table><tr>
+                <td class="ind" indent="0"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="not a date"><a href="item?id=2">a while ago</a></span></span></div>
+                <div class="comment"><span class="commtext c00">No date.</span></div></td>
+              </tr></table></td></tr>
+              <tr class="athing comtr" id="3"><td><table><tr>
+                <td class="ind" indent="0"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:25:00"><a href="item?id=3">on Nov 14, 2021</a></span></span></div>
+                <div class="comment"><span class="commtext c00">Dated.</span></div></td>
+              </tr></table></td></tr>
+            </table>"#,
+        );
+        let mut comments = vec![];
+        extend_comment_tree(&document, &mut comments, &mut vec![], &mut 0, None);
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].id, 3);
+    }
+
+    #[test]
+    fn deleted_comments_are_parsed_as_placeholders() {
+        let document = Html::parse_document(
+            r#"<table class="comment-tree">
+              <tr class="athing comtr" id="29210341"><td><table><tr>
+                <td class="ind" indent="0"><img src="s.gif" height="1" width="0"></td>
+                <td class="default"><div><span class="comhead"><span class="age" title="2021-11-14T09:12:20"><a href="item?id=29210341">on Nov 14, 2021</a></span></span></div>
+                <div class="comment">[deleted]<div class="reply"></div></div></td>
+              </tr></table></td></tr>
+              <tr class="athing comtr" id="29210342"><td><table><tr>
+                <td class="ind" indent="1"><img src="s.gif" height="1" width="40"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:20:00"><a href="item?id=29210342">on Nov 14, 2021</a></span></span></div>
+                <div class="comment"><span class="commtext c00">[flagged] [dead]</span></div></td>
+              </tr></table></td></tr>
+              <tr class="athing comtr" id="29210343"><td><table><tr>
+                <td class="ind" indent="1"><img src="s.gif" height="1" width="40"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:25:00"><a href="item?id=29210343">on Nov 14, 2021</a></span></span></div>
+                <div class="comment"><span class="commtext c00">Still here.</span></div></td>
+              </tr></table></td></tr>
+            </table>"#,
+        );
+        let comments: Vec<Comment> = document
+            .select(&Selector::parse("tr.comtr").unwrap())
+            .map(|tr| extract_comment_info(&tr).unwrap())
+            .collect();
+
+        assert!(comments[0].deleted);
+        assert_eq!(comments[0].user, "".to_string());
+        assert_eq!(comments[0].html_content, "[deleted]".to_string());
+
+        assert!(comments[1].deleted);
+        assert_eq!(comments[1].user, "".to_string());
+        assert_eq!(comments[1].html_content, "[flagged] [dead]".to_string());
+
+        assert!(!comments[2].deleted);
+        assert_eq!(comments[2].user, "someone".to_string());
+        assert_eq!(comments[2].html_content, "Still here.".to_string());
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn auth_is_found_from_user_cookie_in_any_position() {
+        let expires = DateTime::<Utc>::from_str("2022-11-14T09:12:20.000Z").unwrap();
+        let auth = auth_from_cookies(vec![
+            LoginCookie {
+                name: "other".to_string(),
+                value: "abc".to_string(),
+                expires: None,
+            },
+            LoginCookie {
+                name: "user".to_string(),
+                value: "scastiel&xyz".to_string(),
+                expires: Some(expires),
+            },
+        ])
+        .unwrap();
+        assert_eq!(auth.expires, Some(expires));
+        assert_eq!(auth.token(), "other=abc; user=scastiel&xyz".to_string());
+
+        let auth = auth_from_cookies(vec![LoginCookie {
+            name: "user".to_string(),
+            value: "scastiel&xyz".to_string(),
+            expires: None,
+        }])
+        .unwrap();
+        assert_eq!(auth.expires, None);
+
+        let auth = auth_from_cookies(vec![LoginCookie {
+            name: "other".to_string(),
+            value: "abc".to_string(),
+            expires: Some(expires),
+        }]);
+        assert!(auth.is_none());
+    }
+
+    fn first_story(html: &str) -> Story {
+        let document = Html::parse_document(html);
+        let tr = single_doc_element(&document, "tr.athing").unwrap();
+        extract_story_info(&tr).unwrap()
+    }
+
+    #[test]
+    fn scores_and_comment_counts_are_parsed_defensively() {
+        assert_eq!(parse_score("1 point"), Some(1));
+        assert_eq!(parse_score("1,234 points"), Some(1234));
+        assert_eq!(parse_score("points"), None);
+        assert_eq!(parse_comment_count("1&nbsp;comment"), Some(1));
+        assert_eq!(parse_comment_count("12&nbsp;comments"), Some(12));
+        assert_eq!(parse_comment_count("1,234&nbsp;comments"), Some(1234));
+        assert_eq!(parse_comment_count("discuss"), None);
+    }
+
+    #[test]
+    fn story_without_comment_link_has_no_comment_count() {
+        let story = first_story(
+            r#"<table><tr class="athing" id="29246573"><td class="title"><span class="rank">1.</span></td><td class="title"><a href="https://example.com/jobs" class="titlelink">Example (YC S21) is hiring</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span></td></tr></table>"#,
+        );
+        assert_eq!(story.score, None);
+        assert_eq!(story.comment_count, None);
+        assert_eq!(story.kind, StoryKind::Job);
+    }
+
+    #[test]
+    fn unranked_jobs_are_ranked_by_position() {
+        let document = Html::parse_document(
+            r#"<table>
+            <tr class="athing" id="29246573"><td class="title"><span class="rank"></span></td><td class="title"><a href="https://example.com/jobs" class="titlelink">Example (YC S21) is hiring</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span></td></tr>
+            <tr class="spacer" style="height:5px"></tr>
+            <tr class="athing" id="29246574"><td class="title"><span class="rank"></span></td><td class="title"><a href="https://example.org/careers" class="titlelink">Other (YC W20) is hiring engineers</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="age" title="2021-11-17T09:00:00"><a href="item?id=29246574">3 hours ago</a></span></td></tr>
+            </table>"#,
+        );
+        let page = story_page_from_document(&document, 2);
+        assert_eq!(page.stories[&31].id, 29246573);
+        assert_eq!(page.stories[&32].id, 29246574);
+        assert!(page
+            .stories
+            .values()
+            .all(|story| story.kind == StoryKind::Job));
+        // A page 0 doesn't exist, but must not make the ranks underflow.
+        let page = story_page_from_document(&document, 0);
+        assert_eq!(page.stories[&1].id, 29246573);
+    }
+
+    #[test]
+    fn story_rows_can_be_separated_by_whitespace() {
+        assert_eq!(first_story(LINK_STORY_PAGE).score, Some(250));
+        let document = Html::parse_document(
+            r#"<table><tr class="athing" id="1"><td class="title"><a href="https://example.com/" class="titlelink">Alone</a></td></tr>
+            </table>"#,
+        );
+        let tr = single_doc_element(&document, "tr.athing").unwrap();
+        assert!(extract_story_info(&tr).is_none());
+    }
+
+    #[test]
+    fn story_with_discuss_link_has_zero_comments() {
+        let story = first_story(
+            r#"<table><tr class="athing" id="29246573"><td class="title"><span class="rank">1.</span></td><td class="title"><a href="item?id=29246573" class="titlelink">Ask HN: How does QR scanning work?</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="score" id="score_29246573">3 points</span> by <a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span> | <a href="hide?id=29246573&amp;goto=news">hide</a> | <a href="item?id=29246573">discuss</a></td></tr></table>"#,
+        );
+        assert_eq!(story.score, Some(3));
+        assert_eq!(story.user, Some("someone".to_string()));
+        assert_eq!(story.comment_count, Some(0));
+    }
+
+    #[test]
+    fn about_text_has_no_markup() {
+        let user = User {
+            id: "someone".to_string(),
+            created: NaiveDate::from_ymd(2019, 2, 16),
+            karma: 1,
+            about: r#"I write <i>things</i> &amp; code at <a href="https://example.com/a/very/long/path" rel="nofollow">https://example.com/a/very/lo...</a> and <a href="https://b.example.com" rel="nofollow">b</a>.<p>Second paragraph."#.to_string(),
+        };
+        assert_eq!(
+            user.about_text(),
+            "I write things & code at https://example.com/a/very/long/path and https://b.example.com.\n\nSecond paragraph.".to_string()
+        );
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn past_discussions_match_the_same_url_only() {
+        assert!(same_url(
+            "https://www.rust-lang.org/",
+            "http://rust-lang.org"
+        ));
+        assert!(same_url("https://example.com/a/", "https://example.com/a"));
+        assert!(!same_url("https://example.com/a", "https://example.com/ab"));
+        assert!(!same_url(
+            "https://example.com",
+            "https://example.com.evil.org"
+        ));
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn search_hits_are_converted_to_stories() {
+        let story = story_from_search_hit(SearchHit {
+            object_id: "29246573".to_string(),
+            title: Some("Rust".to_string()),
+            url: Some("https://www.rust-lang.org/".to_string()),
+            author: Some("someone".to_string()),
+            points: Some(42),
+            num_comments: None,
+            created_at_i: 1637143200,
+        })
+        .unwrap();
+        assert_eq!(story.id, 29246573);
+        assert_eq!(story.url_displayed, Some("rust-lang.org".to_string()));
+        assert_eq!(story.user, Some("someone".to_string()));
+        assert_eq!(story.score, Some(42));
+        assert_eq!(story.comment_count, None);
+        assert_eq!(story.date, Utc.ymd(2021, 11, 17).and_hms(10, 0, 0));
+        assert_eq!(story.date_displayed, humanize(story.date));
+
+        let without_title = SearchHit {
+            object_id: "29246573".to_string(),
+            title: None,
+            url: Some("https://www.rust-lang.org/".to_string()),
+            author: None,
+            points: None,
+            num_comments: None,
+            created_at_i: 1637143200,
+        };
+        assert!(story_from_search_hit(without_title).is_none());
+    }
+
+    #[test]
+    fn stories_match_their_domain_and_its_parents() {
+        let story = first_story(
+            r#"<table><tr class="athing" id="29246573"><td class="title"><span class="rank">1.</span></td><td class="title"><a href="https://gist.GitHub.com/someone/abc" class="titlelink">A gist</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span></td></tr></table>"#,
+        );
+        assert!(story.matches_domain("gist.github.com"));
+        assert!(story.matches_domain("github.com"));
+        assert!(story.matches_domain("www.GitHub.com"));
+        assert!(!story.matches_domain("hub.com"));
+        assert!(!story.matches_domain("gitlab.com"));
+    }
+
+    fn story_with_url(url: &str) -> Story {
+        Story {
+            id: 1,
+            title: "Title".to_string(),
+            url: Url::from_str(url).unwrap(),
+            url_displayed: None,
+            upvote_auth: None,
+            user: None,
+            score: None,
+            date: Utc.timestamp(0, 0),
+            date_displayed: "on Jan 1, 1970".to_string(),
+            comment_count: None,
+            kind: StoryKind::Regular,
+        }
+    }
+
+    fn comment_at(id: u32, timestamp: i64, parent: Option<&Rc<Comment>>) -> Rc<Comment> {
+        let comment = Rc::new(Comment {
+            id,
+            user: "someone".to_string(),
+            date: Utc.timestamp(timestamp, 0),
+            date_displayed: "on Jan 1, 1970".to_string(),
+            html_content: "Comment".to_string(),
+            deleted: false,
+            collapsed: false,
+            edit_url: None,
+            delete_url: None,
+            parent: RefCell::new(parent.map(Rc::downgrade)),
+            children: RefCell::new(vec![]),
+            thread_size: None,
+        });
+        if let Some(parent) = parent {
+            parent.children.borrow_mut().push(comment.clone());
+        }
+        comment
+    }
+
+    const MY_PROFILE_PAGE: &str = r#"<form class="profileform" method="post" action="/xuser">
+        <input type="hidden" name="id" value="someone"><input type="hidden" name="hmac" value="abc123">
+        <table border="0">
+          <tr class="athing"><td valign="top">user:</td><td><a href="user?id=someone" class="hnuser">someone</a></td></tr>
+          <tr><td valign="top">about:</td><td><textarea cols="60" rows="5" wrap="virtual" name="about">Rust &amp; HN.
+
+https://example.com</textarea></td></tr>
+          <tr><td valign="top">email:</td><td><input type="text" name="uemail" value="someone@example.com" size="60"></td></tr>
+          <tr><td valign="top">showdead:</td><td><select name="showd"><option>no</option><option selected="t">yes</option></select></td></tr>
+          <tr><td valign="top">noprocrast:</td><td><select name="nopro"><option selected="t">no</option><option>yes</option></select></td></tr>
+          <tr><td valign="top">maxvisit:</td><td><input type="text" name="maxv" value="20" size="16"></td></tr>
+          <tr><td valign="top">minaway:</td><td><input type="text" name="minaw" value="180" size="16"></td></tr>
+          <tr><td valign="top">delay:</td><td><input type="text" name="delay" value="0" size="16"></td></tr>
+        </table>
+        <input type="submit" value="update">
+      </form>"#;
+
+    #[test]
+    fn my_profile_is_read_from_the_profile_form() {
+        let fields = profile_form_fields(&Html::parse_document(MY_PROFILE_PAGE)).unwrap();
+        assert_eq!(fields[1], ("hmac".to_string(), "abc123".to_string()));
+        assert!(fields.iter().all(|(name, _)| !name.is_empty()));
+        assert_eq!(
+            my_profile_from_fields(&fields).unwrap(),
+            MyProfile {
+                id: "someone".to_string(),
+                about: "Rust & HN.\n\nhttps://example.com".to_string(),
+                email: Some("someone@example.com".to_string()),
+                show_dead: true,
+                no_procrast: false,
+                max_visit: Some(20),
+                min_away: Some(180),
+                delay: Some(0),
+            }
+        );
+
+        // Other users’ profiles have no form.
+        assert!(profile_form_fields(&Html::parse_document(TEXT_STORY_PAGE)).is_none());
+    }
+
+    #[test]
+    fn comment_pages_have_a_more_token() {
+        let document = Html::parse_document(
+            r#"<table class="comment-tree">
+              <tr class="athing comtr" id="29210342"><td><table><tr>
+                <td class="ind" indent="0"><img src="s.gif" height="1" width="0"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:20:00"><a href="item?id=29210342">on Nov 14, 2021</a></span></span></div>
+                <div class="comment"><span class="commtext c00">A comment.</span></div></td>
+              </tr></table></td></tr>
+              <tr class="morespace" style="height:10px"></tr>
+              <tr><td></td><td><a href="threads?id=someone&amp;next=29210342" class="morelink" rel="next">More</a></td></tr>
+            </table>"#,
+        );
+        let page = comment_page_from_document(&document, 1);
+        assert_eq!(page.comments.len(), 1);
+        assert!(page.has_more);
+        assert_eq!(
+            page.more_token,
+            Some("threads?id=someone&next=29210342".to_string())
+        );
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn login_failures_are_told_apart() {
+        let bad_login = r#"<html><body>Bad login.<br><br><b>Login</b><br><br><form action="login" method="post"><input type="hidden" name="goto" value="news"><table border="0"><tr><td>username:</td><td><input type="text" name="acct"></td></tr><tr><td>password:</td><td><input type="password" name="pw"></td></tr></table><br><input type="submit" value="login"></form></body></html>"#;
+        assert!(login_failure(bad_login).is_none());
+
+        let captcha = r#"<html><head><script src="https://www.google.com/recaptcha/api.js" async defer></script></head><body>Validation required. If this doesn't work, you can email hn@ycombinator.com for help.<br><br><form action="login" method="post"><input type="hidden" name="goto" value="news"><input type="hidden" name="acct" value="someone"><input type="hidden" name="pw" value="secret"><div class="g-recaptcha" data-sitekey="abc"></div><br><input type="submit" value="submit"></form></body></html>"#;
+        match login_failure(captcha) {
+            Some(HnError::Rejected(message)) => assert!(message.contains("captcha")),
+            other => panic!("Unexpected login failure: {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn rate_limit_page_is_detected() {
+        assert!(is_rate_limited(
+            "<html><body>Sorry, we're not able to serve your requests this quickly.</body></html>"
+        ));
+        assert!(!is_rate_limited(TEXT_STORY_PAGE));
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn comment_edit_form_is_read_or_refused() {
+        let document = Html::parse_document(
+            r#"<form method="post" action="xedit"><input type="hidden" name="hmac" value="abc123"><input type="hidden" name="id" value="29210341"><textarea name="text" rows="6" cols="60">My comment.</textarea><br><input type="submit" value="update"></form>"#,
+        );
+        assert_eq!(
+            form_fields(&document, "textarea[name='text']").unwrap(),
+            vec![
+                ("hmac".to_string(), "abc123".to_string()),
+                ("id".to_string(), "29210341".to_string()),
+                ("text".to_string(), "My comment.".to_string()),
+            ]
+        );
+        assert!(matches!(
+            form_refusal(
+                r#"<form action="login" method="post"><input type="text" name="acct"></form>"#,
+                "No form."
+            ),
+            HnError::Unauthenticated
+        ));
+        assert!(matches!(
+            form_refusal("<html><body>Can't edit that.</body></html>", "No form."),
+            HnError::Rejected(message) if message == "Can't edit that."
+        ));
+        assert!(matches!(
+            form_refusal("<html><body></body></html>", "No form."),
+            HnError::Rejected(message) if message == "No form."
+        ));
+    }
+
+    #[test]
+    fn comment_delete_form_is_read_without_its_buttons() {
+        let document = Html::parse_document(
+            r#"<form action="/xdelete" method="post"><input type="hidden" name="id" value="29210341"><input type="hidden" name="hmac" value="abc123"><input type="hidden" name="goto" value="item?id=29210341">Do you want this to be deleted?<br><input type="submit" name="d" value="Yes"> <input type="submit" name="d" value="No"></form>"#,
+        );
+        assert_eq!(
+            form_fields(&document, "input[name='d']").unwrap(),
+            vec![
+                ("id".to_string(), "29210341".to_string()),
+                ("hmac".to_string(), "abc123".to_string()),
+                ("goto".to_string(), "item?id=29210341".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn rejection_message_is_the_page_text() {
+        assert_eq!(
+            rejection_message("<html><body>Unknown or\n expired link.</body></html>"),
+            "Unknown or expired link.".to_string()
+        );
+    }
+
+    #[test]
+    fn hidden_replies_are_detected_with_the_thread_size() {
+        let comment = Rc::new(Comment {
+            thread_size: Some(3),
+            ..Rc::try_unwrap(comment_at(1, 100, None)).unwrap()
+        });
+        comment_at(2, 200, Some(&comment));
+        assert!(comment.has_hidden_replies());
+        comment_at(3, 300, Some(&comment));
+        assert!(!comment.has_hidden_replies());
+    }
+
+    #[test]
+    fn collapsed_comments_are_detected() {
+        let document = Html::parse_document(
+            r#"<table class="comment-tree">
+              <tr class="athing comtr coll" id="29210342"><td><table><tr>
+                <td class="ind" indent="0"><img src="s.gif" height="1" width="0"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=other" class="hnuser">other</a> <span class="age" title="2021-11-14T09:20:00"><a href="item?id=29210342">on Nov 14, 2021</a></span> <a class="togg clicky" id="29210342" n="3" href="javascript:void(0)">[3 more]</a></span></div>
+                <div class="comment noshow"><span class="commtext c00">A reply.</span></div></td>
+              </tr></table></td></tr>
+            </table>"#,
+        );
+        let tr = single_doc_element(&document, "tr.athing").unwrap();
+        assert!(extract_comment_info(&tr).unwrap().collapsed);
+    }
+
+    #[test]
+    fn comments_are_sorted_newest_first() {
+        let old = comment_at(1, 100, None);
+        let recent = comment_at(2, 200, None);
+        comment_at(3, 150, Some(&old));
+        comment_at(4, 300, Some(&old));
+        let mut details = StoryWithDetails {
+            story: story_with_url("https://example.com/"),
+            html_content: None,
+            comments: vec![old, recent],
+            truncated: false,
+        };
+
+        let ids = |comments: &[Rc<Comment>]| comments.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(ids(&details.newest_first()), vec![4, 2, 3, 1]);
+        assert_eq!(details.newest_first()[0].parent().unwrap().id, 1);
+
+        details.sort_newest_first();
+        assert_eq!(ids(details.top_level()), vec![2, 1]);
+        assert_eq!(ids(&details.top_level()[1].children()), vec![4, 3]);
+    }
+
+    #[test]
+    fn dates_are_humanized_as_on_hackernews() {
+        let now = Utc.ymd(2021, 11, 17).and_hms(10, 0, 0);
+        let humanize = |elapsed| humanize_at(now - elapsed, now);
+        assert_eq!(humanize(Duration::seconds(20)), "just now");
+        assert_eq!(humanize(Duration::minutes(1)), "1 minute ago");
+        assert_eq!(humanize(Duration::minutes(59)), "59 minutes ago");
+        assert_eq!(humanize(Duration::hours(2)), "2 hours ago");
+        assert_eq!(humanize(Duration::days(1)), "1 day ago");
+        assert_eq!(humanize(Duration::days(29)), "29 days ago");
+        assert_eq!(humanize(Duration::days(45)), "1 month ago");
+        assert_eq!(humanize(Duration::days(364)), "12 months ago");
+        assert_eq!(humanize(Duration::days(365)), "1 year ago");
+        assert_eq!(humanize(Duration::days(800)), "2 years ago");
+    }
+
+    #[test]
+    fn ages_are_read_from_the_title_or_the_displayed_text() {
+        let date = Utc.ymd(2021, 11, 17).and_hms(10, 0, 0);
+        assert_eq!(parse_age_title("2021-11-17T10:00:00"), Some(date));
+        assert_eq!(
+            parse_age_title("2021-11-17T10:00:00 1637143200"),
+            Some(date)
+        );
+        assert_eq!(parse_age_title("yesterday"), None);
+
+        let now = date;
+        assert_eq!(
+            parse_relative_age("2 hours ago", now),
+            Some(now - Duration::hours(2))
+        );
+        assert_eq!(
+            parse_relative_age("1 day ago", now),
+            Some(now - Duration::days(1))
+        );
+        assert_eq!(parse_relative_age("on Nov 14, 2021", now), None);
+
+        let html = Html::parse_fragment(
+            r#"<table><tr><td><span class="age"><a href="item?id=1">5 minutes ago</a></span></td></tr></table>"#,
+        );
+        let (date, displayed) = parse_age(&html.root_element()).unwrap();
+        assert_eq!(displayed, "5 minutes ago");
+        assert!(Utc::now() - date >= Duration::minutes(5));
+    }
+
+    #[test]
+    fn story_stats_come_from_the_story_header() {
+        let stats = story_stats_from_document(&Html::parse_document(TEXT_STORY_PAGE)).unwrap();
+        assert_eq!(
+            stats,
+            StoryStats {
+                title: "Ask HN: How does QR scanning work?".to_string(),
+                score: Some(3),
+                comment_count: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn clean_url_removes_tracking_params() {
+        let clean_url = |url| story_with_url(url).clean_url().to_string();
+        assert_eq!(
+            clean_url("https://example.com/post?utm_source=hn&id=3&fbclid=abc#top"),
+            "https://example.com/post?id=3#top"
+        );
+        assert_eq!(
+            clean_url("https://example.com/post?utm_source=hn&utm_medium=social"),
+            "https://example.com/post"
+        );
+        assert_eq!(
+            clean_url("https://example.com/post?q=rust"),
+            "https://example.com/post?q=rust"
+        );
+    }
+
+    #[cfg(feature = "firebase")]
+    #[test]
+    fn stories_are_enriched_with_the_official_api() {
+        let mut story = first_story(LINK_STORY_PAGE);
+        story.comment_count = Some(10);
+        enrich_story(
+            &mut story,
+            &FirebaseItem {
+                score: Some(1234),
+                descendants: None,
+            },
+        );
+        assert_eq!(story.score, Some(1234));
+        assert_eq!(story.comment_count, Some(10));
+    }
+
+    #[test]
+    fn comments_url_is_on_hackernews() {
+        assert_eq!(
+            story_with_url("https://example.com/").comments_url(),
+            "https://news.ycombinator.com/item?id=1"
+        );
+    }
+
+    #[test]
+    fn display_domain_strips_subdomains() {
+        let display_domain = |url| story_with_url(url).display_domain();
+        assert_eq!(
+            display_domain("https://gist.github.com/someone"),
+            Some("github.com".to_string())
+        );
+        assert_eq!(
+            display_domain("https://example.com/"),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            display_domain("https://sub.example.com/"),
+            Some("example.com".to_string())
+        );
+        assert_eq!(
+            display_domain("https://www.bbc.co.uk/news"),
+            Some("bbc.co.uk".to_string())
+        );
+        assert_eq!(
+            display_domain("https://bbc.co.uk/"),
+            Some("bbc.co.uk".to_string())
+        );
+        assert_eq!(
+            display_domain("https://foo.github.io/project"),
+            Some("foo.github.io".to_string())
+        );
+        assert_eq!(
+            display_domain("http://localhost:8080/"),
+            Some("localhost".to_string())
+        );
+        assert_eq!(
+            display_domain("http://127.0.0.1:8080/"),
+            Some("127.0.0.1".to_string())
+        );
+    }
+
+    #[test]
+    fn markup_health_reports_missing_selectors() {
+        let document = Html::parse_document(
+            r#"<table><tr class="athing" id="29246573"><td class="title"><span class="rank">1.</span></td><td class="title"><a href="https://example.com/jobs" class="titlelink">Example (YC S21) is hiring</a></td></tr><tr><td colspan="2"></td><td class="subtext"><span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span></td></tr></table>"#,
+        );
+        let health = markup_health(&document);
+        assert!(!health.is_healthy());
+        assert_eq!(
+            health.missing_selectors,
+            vec![
+                ".subtext .score".to_string(),
+                ".subtext .hnuser".to_string()
+            ]
+        );
+
+        let health = markup_health(&Html::parse_document("<p>Redesigned!</p>"));
+        assert_eq!(health.missing_selectors.len(), STORY_LIST_SELECTORS.len());
+    }
+
+    const TEXT_STORY_PAGE: &str = r#"<table class="fatitem" border="0">
+        <tr class="athing" id="29246573">
+          <td align="right" valign="top" class="title"><span class="rank"></span></td>
+          <td valign="top" class="votelinks"><center><a id="up_29246573" href="vote?id=29246573&amp;how=up&amp;goto=item%3Fid%3D29246573"><div class="votearrow" title="upvote"></div></a></center></td>
+          <td class="title"><a href="item?id=29246573" class="titlelink">Ask HN: How does QR scanning work?</a></td>
+        </tr><tr><td colspan="2"></td><td class="subtext"><span class="score" id="score_29246573">3 points</span> by <a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-17T10:00:00"><a href="item?id=29246573">2 hours ago</a></span> | <a href="item?id=29246573">discuss</a></td></tr>
+        <tr style="height:2px"></tr>
+        <tr><td colspan="2"></td><td>What I want to know is how it works.<p>Is there a tutorial?</p></td></tr>
+        <tr style="height:10px"></tr>
+        <tr><td colspan="2"></td><td><form method="post" action="comment"><textarea name="text" rows="6" cols="60"></textarea><br><br><input type="submit" value="add comment"></form></td></tr>
+      </table>"#;
+
+    const LINK_STORY_PAGE: &str = r#"<table class="fatitem" border="0">
+        <tr class="athing" id="27883047">
+          <td align="right" valign="top" class="title"><span class="rank"></span></td>
+          <td class="title"><a href="https://www.hpcwire.com/julia" class="titlelink">Julia Computing raises $24M Series A</a><span class="sitebit comhead"> (<a href="from?site=hpcwire.com"><span class="sitestr">hpcwire.com</span></a>)</span></td>
+        </tr>
+        <tr><td colspan="2"></td><td class="subtext"><span class="score" id="score_27883047">250 points</span> by <a href="user?id=dklend122" class="hnuser">dklend122</a> <span class="age" title="2021-07-19T14:33:05"><a href="item?id=27883047">on July 19, 2021</a></span> | <a href="item?id=27883047">97&nbsp;comments</a></td></tr>
+        <tr style="height:10px"></tr>
+        <tr><td colspan="2"></td><td><form method="post" action="comment"><textarea name="text" rows="6" cols="60"></textarea><br><br><input type="submit" value="add comment"></form></td></tr>
+      </table>"#;
+
+    #[test]
+    fn text_stories_are_recognized_by_their_link() {
+        let page = Html::parse_document(TEXT_STORY_PAGE);
+        let text_story =
+            extract_story_info(&single_doc_element(&page, "tr.athing").unwrap()).unwrap();
+        assert!(text_story.is_text_post());
+        assert!(!story_with_url("https://news.ycombinator.com/item?id=2").is_text_post());
+        assert!(!story_with_url("https://www.hpcwire.com/julia").is_text_post());
+    }
+
+    #[test]
+    fn long_text_stories_keep_their_structure() {
+        let text = r#"I have been programming for a decade, see <a href="https://example.com/about" rel="nofollow">https://example.com/about</a>.<p>First, what I tried:<p><pre><code>  let x = 1;
+  let y = 2;
+</code></pre><p>Then I read <i>the spec</i> and <a href="https://example.com/spec" rel="nofollow">the RFC</a>.<p>Any ideas?"#;
+        let page = TEXT_STORY_PAGE.replace(
+            "What I want to know is how it works.<p>Is there a tutorial?</p>",
+            text,
+        );
+        let html_content = story_text(&Html::parse_document(&page)).unwrap();
+        let fragment = Html::parse_fragment(&html_content);
+        let count = |selector| fragment.select(&Selector::parse(selector).unwrap()).count();
+        assert!(html_content.starts_with("I have been programming for a decade"));
+        assert!(html_content.ends_with("Any ideas?</p>"));
+        assert_eq!(count("p"), 4);
+        assert_eq!(count("a"), 2);
+        assert_eq!(count("pre > code"), 1);
+        assert!(html_content.contains("  let x = 1;\n  let y = 2;\n"));
+    }
+
+    #[test]
+    fn link_stories_can_have_a_text() {
+        let row = r#"<tr style="height:10px"></tr>"#;
+        let with_text = LINK_STORY_PAGE.replacen(
+            row,
+            r#"<tr style="height:2px"></tr><tr><td colspan="2"></td><td><div class="toptext">Some context.</div></td></tr><tr style="height:10px"></tr>"#,
+            1,
+        );
+        let details = story_details_from_pages(&[with_text], None, false).unwrap();
+        assert!(!details.story.is_text_post());
+        assert_eq!(details.html_content, Some("Some context.".to_string()));
+
+        let details =
+            story_details_from_pages(&[LINK_STORY_PAGE.to_string()], None, false).unwrap();
+        assert_eq!(details.html_content, None);
+    }
+
+    #[test]
+    fn story_text_is_found_when_there_is_one() {
+        assert_eq!(
+            story_text(&Html::parse_document(TEXT_STORY_PAGE)),
+            Some("What I want to know is how it works.<p>Is there a tutorial?</p>".to_string())
+        );
+        assert_eq!(story_text(&Html::parse_document(LINK_STORY_PAGE)), None);
+    }
+
+    #[test]
+    fn comment_pages_have_no_story_details() {
+        let page = r#"<table class="fatitem" border="0"><tr class="athing" id="29210341">
+              <td class="ind"></td>
+              <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:12:20"><a href="item?id=29210341">on Nov 14, 2021</a></span> <span class="par"> | <a href="item?id=29210300">parent</a></span></span></div><br>
+              <div class="comment"><span class="commtext c00">The comment.</span></div></td>
+            </tr></table>"#;
+        assert!(story_details_from_pages(&[page.to_string()], None, false).is_none());
+    }
+
+    #[test]
+    fn comment_thread_is_read_from_the_comment_page() {
+        let document = Html::parse_document(
+            r#"<table class="fatitem" border="0"><tr class="athing" id="29210341">
+              <td class="ind"></td>
+              <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:12:20"><a href="item?id=29210341">on Nov 14, 2021</a></span> <span class="par"> | <a href="item?id=29210300">parent</a></span></span></div><br>
+              <div class="comment"><span class="commtext c00">The comment.</span></div></td>
+            </tr></table>
+            <table class="comment-tree">
+              <tr class="athing comtr" id="29210342"><td><table><tr>
+                <td class="ind" indent="0"><img src="s.gif" height="1" width="0"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=other" class="hnuser">other</a> <span class="age" title="2021-11-14T09:20:00"><a href="item?id=29210342">on Nov 14, 2021</a></span> <a class="togg clicky" id="29210342" n="2" href="javascript:void(0)">[–]</a></span></div>
+                <div class="comment"><span class="commtext c00">A reply.</span></div></td>
+              </tr></table></td></tr>
+              <tr class="athing comtr" id="29210343"><td><table><tr>
+                <td class="ind" indent="1"><img src="s.gif" height="1" width="40"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id=someone" class="hnuser">someone</a> <span class="age" title="2021-11-14T09:25:00"><a href="item?id=29210343">on Nov 14, 2021</a></span></span></div>
+                <div class="comment"><span class="commtext c00">A reply to the reply.</span></div></td>
+              </tr></table></td></tr>
+            </table>"#,
+        );
+        let comment = comment_thread_from_document(&document).unwrap();
+        assert_eq!(comment.id, 29210341);
+        assert_eq!(comment.user, "someone".to_string());
+        assert_eq!(comment.html_content, "The comment.".to_string());
+        let replies = comment.children();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].id, 29210342);
+        assert_eq!(replies[0].parent().unwrap().id, 29210341);
+        assert_eq!(replies[0].children()[0].id, 29210343);
+        assert_eq!(comment.depth(), 0);
+        assert_eq!(replies[0].depth(), 1);
+        assert_eq!(replies[0].children()[0].depth(), 2);
+        assert!(!replies[0].has_hidden_replies());
+        assert!(!replies[0].collapsed);
+
+        assert!(comment_thread_from_document(&Html::parse_document(TEXT_STORY_PAGE)).is_none());
+    }
+
+    #[test]
+    fn own_comments_have_edit_and_delete_links() {
+        let document = Html::parse_document(
+            r#"<table class="fatitem" border="0"><tr class="athing" id="29210341">
+              <td class="ind"></td>
+              <td class="default"><div><span class="comhead"><a href="user?id=me" class="hnuser">me</a> <span class="age" title="2021-11-14T09:12:20"><a href="item?id=29210341">5 minutes ago</a></span> <span class="navs"> | <a href="edit?id=29210341">edit</a> | <a href="delete-confirm?id=29210341&amp;goto=item%3Fid%3D29210341">delete</a></span></span></div><br>
+              <div class="comment"><span class="commtext c00">My comment.</span></div></td>
+            </tr></table>"#,
+        );
+        let comment = comment_thread_from_document(&document).unwrap();
+        assert_eq!(
+            comment.edit_url.as_ref().map(Url::as_str),
+            Some("https://news.ycombinator.com/edit?id=29210341")
+        );
+        assert_eq!(
+            comment.delete_url.as_ref().map(Url::as_str),
+            Some(
+                "https://news.ycombinator.com/delete-confirm?id=29210341&goto=item%3Fid%3D29210341"
+            )
+        );
+        assert!(comment.children().is_empty());
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn only_replies_after_the_cutoff_are_counted() {
+        let comment = |id: u32, user: &str, indent: u32, time: &str| {
+            format!(
+                r#"<tr class="athing comtr" id="{id}"><td><table><tr>
+                <td class="ind" indent="{indent}"></td>
+                <td class="default"><div><span class="comhead"><a href="user?id={user}" class="hnuser">{user}</a> <span class="age" title="2021-11-17T{time}"><a href="item?id={id}">1 hour ago</a></span></span></div>
+                <div class="comment"><span class="commtext c00">Text.</span></div></td>
+              </tr></table></td></tr>"#
+            )
+        };
+        let document = Html::parse_document(&format!(
+            r#"<table class="comment-tree">{}{}{}{}</table>"#,
+            comment(1, "me", 0, "08:00:00"),
+            comment(2, "someone", 1, "09:00:00"),
+            comment(3, "someone", 1, "09:00:01"),
+            comment(4, "me", 1, "09:30:00"),
+        ));
+        let mut comments = vec![];
+        extend_comment_tree(&document, &mut comments, &mut vec![], &mut 0, None);
+        let cutoff = DateTime::from_str("2021-11-17T09:00:00.000Z").unwrap();
+        assert_eq!(count_replies(&comments, "me", &cutoff), 1);
+        let before = DateTime::from_str("2021-11-17T08:59:59.000Z").unwrap();
+        assert_eq!(count_replies(&comments, "me", &before), 2);
+    }
+
+    #[cfg(feature = "fetch")]
+    #[test]
+    fn activity_is_counted_per_day_oldest_first() {
+        let dates: Vec<DateTime<Utc>> = [
+            "2021-11-17T10:00:00.000Z",
+            "2021-11-15T23:59:00.000Z",
+            "2021-11-17T08:00:00.000Z",
+        ]
+        .iter()
+        .map(|date| DateTime::from_str(date).unwrap())
+        .collect();
+        assert_eq!(
+            activity_by_day(&dates),
+            vec![
+                (NaiveDate::from_ymd(2021, 11, 15), 1),
+                (NaiveDate::from_ymd(2021, 11, 17), 2)
+            ]
+        );
+        assert_eq!(activity_by_day(&[]), vec![]);
+    }
+
+    #[test]
+    fn owned_comments_keep_the_tree_and_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        let parent = Rc::new(Comment {
+            id: 1,
+            user: "someone".to_string(),
+            date: Utc.timestamp(0, 0),
+            date_displayed: "on Jan 1, 1970".to_string(),
+            html_content: "Parent".to_string(),
+            deleted: false,
+            collapsed: false,
+            edit_url: None,
+            delete_url: None,
+            parent: RefCell::new(None),
+            children: RefCell::new(vec![]),
+            thread_size: None,
+        });
+        let child = Rc::new(Comment {
+            id: 2,
+            user: "someone_else".to_string(),
+            date: Utc.timestamp(0, 0),
+            date_displayed: "on Jan 1, 1970".to_string(),
+            html_content: "Child".to_string(),
+            deleted: false,
+            collapsed: false,
+            edit_url: None,
+            delete_url: None,
+            parent: RefCell::new(Some(Rc::downgrade(&parent))),
+            children: RefCell::new(vec![]),
+            thread_size: None,
+        });
+        parent.children.borrow_mut().push(child);
+
+        let owned = OwnedComment::from(parent.as_ref());
+        assert_send_sync(&owned);
+        assert_eq!(owned.id, 1);
+        assert_eq!(owned.children.len(), 1);
+        assert_eq!(owned.children[0].id, 2);
+        assert_eq!(owned.children[0].html_content, "Child".to_string());
+    }
+
+    #[test]
+    fn reading_time_counts_the_article_paragraphs() {
+        let words = "word ".repeat(250);
+        let html = format!(
+            "<body><nav><p>Home About</p></nav><article><p>{}</p><p>Last words</p></article></body>",
+            words
+        );
+        assert_eq!(parse::article_word_count(&html), 252);
+        assert_eq!(parse::article_word_count("<p>Three short words</p>"), 3);
+        assert_eq!(text_word_count("First paragraph<p>Second <i>one</i>"), 4);
+        assert_eq!(reading_time(252), 2);
+        assert_eq!(reading_time(0), 1);
+    }
 }
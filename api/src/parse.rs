@@ -0,0 +1,89 @@
+//! Read the pages of HackerNews without requesting them, e.g. in a web frontend that
+//! fetches the pages itself. These functions take the HTML of a page, and return the
+//! same information as their counterparts at the root of the crate.
+//!
+//! Unlike the rest of the crate, this module doesn’t need the `fetch` feature. With the
+//! default features disabled, the crate depends neither on reqwest nor on Tokio, and
+//! can be built for WebAssembly (`wasm32-unknown-unknown`).
+//!
+//! ## Example
+//!
+//! ```
+//! use hnapi::parse;
+//!
+//! let html = "<html><body><table id=\"hnmain\"></table></body></html>";
+//! assert!(parse::stories_page(html, 1).stories.is_empty());
+//! assert!(parse::user_details(html).is_none());
+//! ```
+
+use crate::{
+    article_word_count_from_document, comment_page_from_document, comment_thread_from_document,
+    comments_page_info, my_profile_from_fields, profile_form_fields, story_details_from_pages,
+    story_page_from_document, story_stats_from_document, user_from_document, Comment, CommentPage,
+    MarkupHealth, MyProfile, StoryPage, StoryStats, StoryWithDetails, User,
+};
+use scraper::Html;
+use std::rc::Rc;
+use url::Url;
+
+/// Stories of a page of a list (e.g. `https://news.ycombinator.com/news?p=2`) or of the
+/// submissions of a user, `page` being the number of the page.
+pub fn stories_page(html: &str, page: usize) -> StoryPage {
+    story_page_from_document(&Html::parse_document(html), page)
+}
+
+/// Number of words of the main text of an article, from the HTML of its page, e.g. to
+/// estimate its reading time with [`reading_time`](crate::reading_time).
+pub fn article_word_count(html: &str) -> usize {
+    article_word_count_from_document(&Html::parse_document(html))
+}
+
+/// Details and comments of a story, from the HTML of its page
+/// (`https://news.ycombinator.com/item?id=…`) followed by the ones of the next pages,
+/// given by [`next_item_page_url`]. Will return `None` if the first page is not the one
+/// of a story.
+pub fn story_details(pages: &[String]) -> Option<StoryWithDetails> {
+    story_details_from_pages(pages, None, false)
+}
+
+/// URL of the next page of the comments of an item, when HackerNews splits them over
+/// several pages.
+pub fn next_item_page_url(html: &str) -> Option<Url> {
+    comments_page_info(html).1
+}
+
+/// Title, score and comment count of a story, from its page. Will return `None` if it
+/// is not the page of a story.
+pub fn story_stats(html: &str) -> Option<StoryStats> {
+    story_stats_from_document(&Html::parse_document(html))
+}
+
+/// Comment and the replies displayed on its page. Will return `None` if it is not the
+/// page of a comment.
+pub fn comment_thread(html: &str) -> Option<Rc<Comment>> {
+    comment_thread_from_document(&Html::parse_document(html))
+}
+
+/// Comments of a page of the threads of a user (e.g.
+/// `https://news.ycombinator.com/threads?id=pg`), `page` being the number of the page.
+pub fn comments_page(html: &str, page: usize) -> CommentPage {
+    comment_page_from_document(&Html::parse_document(html), page)
+}
+
+/// Details about a user, from their page. Will return `None` for a non-existent user.
+pub fn user_details(html: &str) -> Option<User> {
+    user_from_document(&Html::parse_document(html))
+}
+
+/// Settings of a user, from the profile page HackerNews displays to the user
+/// themselves. Will return `None` if the page doesn’t have the form to edit them.
+pub fn my_profile(html: &str) -> Option<MyProfile> {
+    profile_form_fields(&Html::parse_document(html))
+        .and_then(|fields| my_profile_from_fields(&fields))
+}
+
+/// Selectors the parser relies on that are missing from a page listing stories, like
+/// [`check_markup`](crate::check_markup) does with the front page.
+pub fn markup_health(html: &str) -> MarkupHealth {
+    crate::markup_health(&Html::parse_document(html))
+}
@@ -1,11 +1,25 @@
+use crate::state::{Snapshot, StoryPosition};
+use chrono::{DateTime, Utc};
 use console::style;
-use hnapi::{Comment, Story, StoryWithDetails, User};
-use html_escape::decode_html_entities;
+use hnapi::{Comment, Story, StoryKind, StoryWithDetails, User};
 use hyphenation::{Language, Load, Standard};
-use regex::Regex;
-use textwrap::{fill, Options};
+use regex::{Captures, Regex};
+use scraper::{ElementRef, Html};
+use serde::Serialize;
+use textwrap::{fill, word_separators::AsciiSpace, word_splitters::WordSplitter, Options};
 use url::Url;
 
+/// How the dates of stories and comments are displayed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DateFormat {
+    /// As HackerNews displays them, e.g. “2 months ago”.
+    Relative,
+    /// ISO 8601, e.g. “2021-11-17T10:00:00+00:00”.
+    Iso,
+    /// Unix timestamp, e.g. “1637143200”.
+    Unix,
+}
+
 pub fn format_user(user: &User) -> String {
     format!(
         "{}{}\n{}{}\n{}{}\n{}{}\n",
@@ -19,7 +33,7 @@ pub fn format_user(user: &User) -> String {
         format_story_text(&user.about, 0)
             .lines()
             .enumerate()
-            .map(|(i, line)| if i == 0 {
+            .map(|(i, line)| if i == 0 || line.is_empty() {
                 line.to_string()
             } else {
                 format!("         {}", line)
@@ -29,38 +43,129 @@ pub fn format_user(user: &User) -> String {
     )
 }
 
-pub fn format_story(rank: usize, story: &Story) -> String {
+pub fn format_story(rank: usize, story: &Story, date_format: DateFormat) -> String {
     format!(
         "{:2}. ▲ {} {}\n      {}",
         rank,
-        format_story_title(&story.title),
+        hyperlink(&format_story_title(&story.title), story.url.as_str()),
         format_story_short_url(story),
-        format_second_line(story),
+        format_second_line(story, date_format),
     )
 }
 
-pub fn format_story_details(details: &StoryWithDetails) -> String {
+/// What changed for a story since its list was last shown: whether it is new, how
+/// many ranks it moved, and how many points it got. Empty if nothing changed.
+pub fn format_story_change(rank: usize, story: &Story, previous: Option<&StoryPosition>) -> String {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return style("new").green().bold().to_string(),
+    };
+    let mut changes = vec![];
+    if rank < previous.rank {
+        changes.push(
+            style(format!("↑{}", previous.rank - rank))
+                .green()
+                .to_string(),
+        );
+    } else if rank > previous.rank {
+        changes.push(
+            style(format!("↓{}", rank - previous.rank))
+                .red()
+                .to_string(),
+        );
+    }
+    let delta = i64::from(story.score.unwrap_or(0)) - i64::from(previous.score.unwrap_or(0));
+    if delta != 0 {
+        changes.push(style(format!("{:+} points", delta)).dim().to_string());
+    }
+    changes.join(" ")
+}
+
+/// How the rank and score of a story evolved, one snapshot per line with the change
+/// of score since the previous one.
+pub fn format_trend(snapshots: &[Snapshot], date_format: DateFormat) -> String {
+    let mut previous_score = None;
+    snapshots
+        .iter()
+        .map(|snapshot| {
+            let score = snapshot.score.unwrap_or(0);
+            let delta = previous_score
+                .map(|previous: u32| {
+                    let delta = i64::from(score) - i64::from(previous);
+                    style(format!(" ({:+})", delta)).dim().to_string()
+                })
+                .unwrap_or_default();
+            previous_score = Some(score);
+            format!(
+                "{}  #{:<2} {} points{}",
+                style(format_date(&snapshot.date, "", date_format)).dim(),
+                snapshot.rank,
+                score,
+                delta
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[derive(Serialize)]
+struct RankedStory<'a> {
+    rank: usize,
+    #[serde(flatten)]
+    story: &'a Story,
+}
+
+/// Story as a single line of JSON, with its rank, to be read by other programs.
+pub fn format_story_jsonl(rank: usize, story: &Story) -> String {
+    serde_json::to_string(&RankedStory { rank, story }).expect("Stories can be serialized")
+}
+
+/// Details of a story, with the estimated reading time (in minutes) of its article when
+/// given, and the matches of `highlight` in its text highlighted.
+pub fn format_story_details(
+    details: &StoryWithDetails,
+    date_format: DateFormat,
+    reading_time: Option<u32>,
+    highlight: Option<&Regex>,
+) -> String {
     format!(
-        "▲ {}\n  {}{}{}",
-        format_story_title(&details.story.title),
-        format_second_line(&details.story),
+        "▲ {}\n  {}{}{}{}",
+        hyperlink(
+            &format_story_title(&details.story.title),
+            details.story.url.as_str()
+        ),
+        format_second_line(&details.story, date_format),
         format!("\n  ↳ {}", format_story_url(&details.story.url)),
+        reading_time
+            .map(|minutes| style(format!(" · ~{} min read", minutes)).dim().to_string())
+            .unwrap_or_default(),
         details
             .html_content
             .as_deref()
-            .map(|text| format!("\n\n{}", format_story_text(text, 0)))
+            .map(|text| format!("\n\n{}", format_highlighted_text(text, 0, highlight)))
             .unwrap_or_else(|| "".to_string()),
     )
 }
 
-pub fn format_comment(comment: &Comment, level: usize) -> String {
+pub fn format_comment(
+    comment: &Comment,
+    level: usize,
+    date_format: DateFormat,
+    highlight: Option<&Regex>,
+) -> String {
     format!(
         "{}\n{}",
-        indent(&format_comment_header(comment), level),
-        format_story_text(&comment.html_content, level),
+        indent(&format_comment_header(comment, date_format), level),
+        format_highlighted_text(&comment.html_content, level, highlight),
     )
 }
 
+/// Summary of the stories or comments hidden because they are muted, so that they don't
+/// vanish silently.
+pub fn format_hidden(count: usize) -> String {
+    style(format!("[{} hidden]", count)).dim().to_string()
+}
+
 pub fn indent(text: &str, level: usize) -> String {
     text.lines()
         .map(|line| format!("{}{}", "  ".repeat(level), line))
@@ -69,21 +174,139 @@ pub fn indent(text: &str, level: usize) -> String {
 }
 
 fn format_story_text(text: &str, level: usize) -> String {
-    let text = text.replace("<p>", "\n\n").replace("</p>", "");
-    let text = decode_html_entities(&text);
-    let text = Regex::new("<a [^>]*href=\".*\">(.*)</a>")
-        .unwrap()
-        .replace_all(&text, style("$1").dim().to_string());
-    let text = Regex::new("<i>(.*)</i>")
-        .unwrap()
-        .replace_all(&text, style("$1").italic().to_string());
-    indent(&wrap_text(text.trim(), 80 - level * 2), level)
+    format_highlighted_text(text, level, None)
+}
+
+/// Render the HTML of a story or a comment, highlighting the matches of `highlight` in
+/// its text (links and code blocks are left as is).
+fn format_highlighted_text(text: &str, level: usize, highlight: Option<&Regex>) -> String {
+    let fragment = Html::parse_fragment(text);
+    let width = 80 - level * 2;
+    let mut blocks: Vec<String> = vec![];
+    let mut paragraph = String::new();
+    for child in fragment.root_element().children() {
+        match ElementRef::wrap(child) {
+            Some(el) if el.value().name() == "p" => {
+                blocks.push(wrap_text(paragraph.trim(), width));
+                paragraph = format_inline(el, highlight);
+            }
+            Some(el) if el.value().name() == "pre" => {
+                blocks.push(wrap_text(paragraph.trim(), width));
+                paragraph = String::new();
+                blocks.push(format_code(&el.text().collect::<String>()));
+            }
+            Some(el) => paragraph.push_str(&format_inline_element(el, highlight)),
+            None => {
+                if let Some(text) = child.value().as_text() {
+                    paragraph.push_str(&highlight_matches(text, highlight));
+                }
+            }
+        }
+    }
+    blocks.push(wrap_text(paragraph.trim(), width));
+    let blocks: Vec<String> = blocks
+        .into_iter()
+        .filter(|block| !block.is_empty())
+        .collect();
+    indent(&blocks.join("\n\n"), level)
+}
+
+fn highlight_matches(text: &str, highlight: Option<&Regex>) -> String {
+    match highlight {
+        Some(regex) => regex
+            .replace_all(text, |captures: &Captures| {
+                style(&captures[0]).black().on_yellow().to_string()
+            })
+            .into_owned(),
+        None => text.to_string(),
+    }
+}
+
+fn format_inline(el: ElementRef, highlight: Option<&Regex>) -> String {
+    el.children()
+        .map(|child| {
+            if let Some(text) = child.value().as_text() {
+                highlight_matches(text, highlight)
+            } else if let Some(child_el) = ElementRef::wrap(child) {
+                format_inline_element(child_el, highlight)
+            } else {
+                "".to_string()
+            }
+        })
+        .collect()
+}
+
+fn format_inline_element(el: ElementRef, highlight: Option<&Regex>) -> String {
+    // The text of links is compared to their URL, so it is not highlighted.
+    if el.value().name() == "a" {
+        return format_link(&format_inline(el, None), el.value().attr("href"));
+    }
+    let content = format_inline(el, highlight);
+    match el.value().name() {
+        "i" | "em" => style(content).italic().to_string(),
+        "b" | "strong" => style(content).bold().to_string(),
+        "code" => style(unbreakable_code(&content)).cyan().to_string(),
+        _ => content,
+    }
+}
+
+/// Links are followed by their URL, unless their text is already the URL, that HN
+/// truncates when it is long (e.g. “https://example.com/a/very/lo...”).
+fn format_link(text: &str, href: Option<&str>) -> String {
+    match href {
+        Some(href) if href.starts_with(text.trim_end_matches("...")) => {
+            style(href).dim().to_string()
+        }
+        Some(href) => style(format!("{} ({})", text, href)).dim().to_string(),
+        None => style(text).dim().to_string(),
+    }
+}
+
+/// Code blocks are not wrapped, to keep their indentation and line breaks.
+fn format_code(code: &str) -> String {
+    code.trim_end()
+        .lines()
+        .map(|line| style(line).cyan().to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Marks the end of an inline code span, so that `ProseSplitter` doesn’t hyphenate it.
+/// Removed once the text is wrapped.
+const CODE_END: char = '\u{2060}';
+
+/// Inline code as a single word for `wrap_text`: its spaces are replaced with
+/// non-breaking ones (restored once the text is wrapped) and its end is marked.
+fn unbreakable_code(code: &str) -> String {
+    format!("{}{}", code.replace(' ', "\u{a0}"), CODE_END)
 }
 
+/// Lines are only broken at spaces, or where `ProseSplitter` hyphenates a word.
 fn wrap_text(text: &str, width: usize) -> String {
     let dictionary = Standard::from_embedded(Language::EnglishUS).unwrap();
-    let options = Options::new(width).word_splitter(dictionary);
+    let options = Options::new(width)
+        .word_separator(AsciiSpace)
+        .word_splitter(ProseSplitter(dictionary))
+        .break_words(false);
     fill(text, &options)
+        .replace(CODE_END, "")
+        .replace('\u{a0}', " ")
+}
+
+/// Hyphenates words, except URLs and inline code which would not be usable anymore
+/// once split. Long words are not broken either (see `break_words` in `wrap_text`), so
+/// URLs longer than a line overflow it rather than being cut.
+#[derive(Clone, Debug)]
+struct ProseSplitter(Standard);
+
+impl WordSplitter for ProseSplitter {
+    fn split_points(&self, word: &str) -> Vec<usize> {
+        if word.contains("://") || word.contains("www.") || word.contains(CODE_END) {
+            vec![]
+        } else {
+            self.0.split_points(word)
+        }
+    }
 }
 
 fn format_story_title(story_title: &str) -> String {
@@ -91,18 +314,69 @@ fn format_story_title(story_title: &str) -> String {
 }
 
 fn format_story_short_url(story: &Story) -> String {
+    if story.is_text_post() {
+        return hyperlink(
+            &style(format!("[{}]", text_post_tag(&story.title)))
+                .dim()
+                .to_string(),
+            story.url.as_str(),
+        );
+    }
     story
         .url_displayed
         .as_deref()
-        .map(|url| style(format!("({})", url)).dim().to_string())
+        .map(|url| {
+            hyperlink(
+                &style(format!("({})", url)).dim().to_string(),
+                story.url.as_str(),
+            )
+        })
         .unwrap_or_default()
 }
 
+/// Tag displayed instead of the domain for text posts, which link to their own
+/// discussion: “ask” and “show” for “Ask HN” and “Show HN” posts, “text” otherwise.
+fn text_post_tag(title: &str) -> &'static str {
+    if title.starts_with("Ask HN") {
+        "ask"
+    } else if title.starts_with("Show HN") {
+        "show"
+    } else {
+        "text"
+    }
+}
+
+/// Wrap a text in an OSC 8 hyperlink, that modern terminals make clickable (others
+/// just display the text). Plain text is returned when styles are disabled, e.g. with
+/// `--no-color` or when the output is not a terminal.
+fn hyperlink(text: &str, url: &str) -> String {
+    if console::colors_enabled() {
+        format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+    } else {
+        text.to_string()
+    }
+}
+
 fn format_story_url(story_url: &Url) -> String {
     style(story_url).to_string()
 }
 
-fn format_second_line(story: &Story) -> String {
+fn format_date(date: &DateTime<Utc>, date_displayed: &str, date_format: DateFormat) -> String {
+    match date_format {
+        DateFormat::Relative if date_displayed.is_empty() => hnapi::humanize(*date),
+        DateFormat::Relative => date_displayed.to_string(),
+        DateFormat::Iso => date.to_rfc3339(),
+        DateFormat::Unix => date.timestamp().to_string(),
+    }
+}
+
+fn format_second_line(story: &Story, date_format: DateFormat) -> String {
+    if story.kind == StoryKind::Job {
+        return style(format_date(&story.date, &story.date_displayed, date_format))
+            .dim()
+            .italic()
+            .to_string();
+    }
     style(format!(
         "{} points{} {} | {} comments",
         story.score.unwrap_or(0),
@@ -111,7 +385,7 @@ fn format_second_line(story: &Story) -> String {
             .as_deref()
             .map(|by| format!(" by {}", by))
             .unwrap_or_else(|| "".to_string()),
-        story.date_displayed,
+        format_date(&story.date, &story.date_displayed, date_format),
         story.comment_count.unwrap_or(0)
     ))
     .dim()
@@ -119,9 +393,225 @@ fn format_second_line(story: &Story) -> String {
     .to_string()
 }
 
-fn format_comment_header(comment: &Comment) -> String {
-    style(format!("{} {}", comment.user, comment.date_displayed,))
-        .dim()
-        .italic()
-        .to_string()
+fn format_comment_header(comment: &Comment, date_format: DateFormat) -> String {
+    style(format!(
+        "{} {}",
+        comment.user,
+        format_date(&comment.date, &comment.date_displayed, date_format)
+    ))
+    .dim()
+    .italic()
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separate_italic_phrases_are_styled_separately() {
+        console::set_colors_enabled(true);
+        assert_eq!(
+            format_story_text("I <i>really</i> think it is <i>fine</i>.", 0),
+            format!(
+                "I {} think it is {}.",
+                style("really").italic(),
+                style("fine").italic()
+            )
+        );
+    }
+
+    #[test]
+    fn paragraphs_and_nested_styles_are_rendered() {
+        console::set_colors_enabled(true);
+        assert_eq!(
+            format_story_text("First &amp; <b>bold <i>both</i></b><p>Second", 1),
+            format!(
+                "  First & {}\n  \n  Second",
+                style(format!("bold {}", style("both").italic())).bold()
+            )
+        );
+    }
+
+    #[test]
+    fn multiple_links_are_rendered_separately() {
+        console::set_colors_enabled(true);
+        assert_eq!(
+            format_story_text(
+                r#"See <a href="https://a.example.com" rel="nofollow">this</a> and <a href="https://b.example.com" rel="nofollow">that</a>."#,
+                0
+            ),
+            format!(
+                "See {} and {}.",
+                style("this (https://a.example.com)").dim(),
+                style("that (https://b.example.com)").dim()
+            )
+        );
+    }
+
+    #[test]
+    fn user_about_with_several_links_and_paragraphs_is_rendered() {
+        console::set_colors_enabled(true);
+        let user = User {
+            id: "someone".to_string(),
+            created: chrono::NaiveDate::from_ymd(2019, 2, 16),
+            karma: 1,
+            about: r#"Blog: <a href="https://a.example.com" rel="nofollow">https://a.example.com</a> and <a href="https://b.example.com" rel="nofollow">code</a><p>Second paragraph"#.to_string(),
+        };
+        assert!(format_user(&user).ends_with(&format!(
+            "Blog: {} and {}\n\n         Second paragraph\n",
+            style("https://a.example.com").dim(),
+            style("code (https://b.example.com)").dim()
+        )));
+    }
+
+    #[test]
+    fn truncated_urls_are_rendered_in_full() {
+        console::set_colors_enabled(true);
+        assert_eq!(
+            format_story_text(
+                r#"<a href="https://example.com/a/very/long/path" rel="nofollow">https://example.com/a/very/lo...</a>"#,
+                0
+            ),
+            style("https://example.com/a/very/long/path")
+                .dim()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn dates_are_formatted_as_requested() {
+        let date = DateTime::parse_from_rfc3339("2021-11-17T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            format_date(&date, "2 hours ago", DateFormat::Relative),
+            "2 hours ago".to_string()
+        );
+        assert_eq!(
+            format_date(&date, "2 hours ago", DateFormat::Iso),
+            "2021-11-17T10:00:00+00:00".to_string()
+        );
+        assert_eq!(
+            format_date(&date, "2 hours ago", DateFormat::Unix),
+            "1637143200".to_string()
+        );
+    }
+
+    #[test]
+    fn stories_are_formatted_as_json_lines() {
+        let story = Story {
+            id: 29246573,
+            title: "Rust".to_string(),
+            url: Url::parse("https://www.rust-lang.org/").unwrap(),
+            url_displayed: Some("rust-lang.org".to_string()),
+            upvote_auth: None,
+            user: Some("someone".to_string()),
+            score: Some(42),
+            date: DateTime::parse_from_rfc3339("2021-11-17T10:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            date_displayed: "2 hours ago".to_string(),
+            comment_count: Some(3),
+            kind: StoryKind::Regular,
+        };
+        let line = format_story_jsonl(1, &story);
+        assert!(!line.contains('\n'));
+        let json: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(json["rank"], 1);
+        assert_eq!(json["id"], 29246573);
+        assert_eq!(json["url"], "https://www.rust-lang.org/");
+    }
+
+    #[test]
+    fn story_changes_show_moves_and_score_deltas() {
+        console::set_colors_enabled(true);
+        let mut story = Story {
+            id: 29246573,
+            title: "Rust".to_string(),
+            url: Url::parse("https://www.rust-lang.org/").unwrap(),
+            url_displayed: Some("rust-lang.org".to_string()),
+            upvote_auth: None,
+            user: Some("someone".to_string()),
+            score: Some(42),
+            date: DateTime::parse_from_rfc3339("2021-11-17T10:00:00Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            date_displayed: "2 hours ago".to_string(),
+            comment_count: Some(3),
+            kind: StoryKind::Regular,
+        };
+        let previous = StoryPosition {
+            rank: 5,
+            score: Some(30),
+        };
+        assert_eq!(
+            format_story_change(2, &story, None),
+            style("new").green().bold().to_string()
+        );
+        assert_eq!(
+            format_story_change(2, &story, Some(&previous)),
+            format!("{} {}", style("↑3").green(), style("+12 points").dim())
+        );
+        story.score = Some(30);
+        assert_eq!(
+            format_story_change(7, &story, Some(&previous)),
+            style("↓2").red().to_string()
+        );
+        assert_eq!(format_story_change(5, &story, Some(&previous)), "");
+    }
+
+    #[test]
+    fn text_posts_are_tagged_from_their_title() {
+        assert_eq!(text_post_tag("Ask HN: What are you working on?"), "ask");
+        assert_eq!(text_post_tag("Show HN: My side project"), "show");
+        assert_eq!(text_post_tag("Tell HN: Something happened"), "text");
+    }
+
+    #[test]
+    fn hyperlinks_use_osc_8_sequences() {
+        console::set_colors_enabled(true);
+        assert_eq!(
+            hyperlink("title", "https://example.com"),
+            "\x1b]8;;https://example.com\x1b\\title\x1b]8;;\x1b\\".to_string()
+        );
+    }
+
+    #[test]
+    fn urls_and_inline_code_are_not_split() {
+        let url = "https://example.com/a/very/long/path/to/a/page/that/does/not/fit/on/a/line.html";
+        let text = format!(
+            "Have a look at {} which explains everything, then call <code>HashMap::with_capacity(expected_number_of_entries)</code> and <code>cargo build --release</code> once.",
+            url
+        );
+        let wrapped = console::strip_ansi_codes(&format_story_text(&text, 0)).to_string();
+        assert!(wrapped.lines().any(|line| line.contains(url)));
+        assert!(wrapped
+            .lines()
+            .any(|line| line.contains("HashMap::with_capacity(expected_number_of_entries)")));
+        assert!(wrapped
+            .lines()
+            .any(|line| line.contains("cargo build --release")));
+        assert!(!wrapped.contains('\u{a0}'));
+    }
+
+    #[test]
+    fn highlighted_words_are_styled() {
+        console::set_colors_enabled(true);
+        let highlight = Regex::new(r"(?i)\brust\b").unwrap();
+        let highlighted = |text| style(text).black().on_yellow().to_string();
+        assert_eq!(
+            format_highlighted_text(
+                "Rust, rustc and <i>rust</i><p>Trust <code>rust</code>",
+                0,
+                Some(&highlight)
+            ),
+            format!(
+                "{}, rustc and {}\n\nTrust {}",
+                highlighted("Rust"),
+                style(highlighted("rust")).italic(),
+                style(highlighted("rust")).cyan()
+            )
+        );
+    }
 }
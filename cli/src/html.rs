@@ -0,0 +1,130 @@
+use hnapi::{Comment, StoryWithDetails};
+use std::fmt::Write;
+use std::rc::Rc;
+
+/// Render a story and its comments as a standalone HTML document, e.g. to archive a
+/// discussion. The scraped fields (title, users, dates and contents) are already HTML
+/// and are kept as HackerNews serves them; only URLs are escaped. Replies are nested in
+/// lists with a `depth-N` class.
+pub fn format_story_details_html(details: &StoryWithDetails) -> String {
+    let story = &details.story;
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+</head>
+<body>
+<article>
+<header>
+<h1><a href="{url}">{title}</a></h1>
+<p class="meta">{score} points{user} <a href="{comments_url}"><time datetime="{date}">{date_displayed}</time></a> | {comment_count} comments</p>
+</header>
+"#,
+        title = story.title,
+        url = escape(story.url.as_str()),
+        score = story.score.unwrap_or(0),
+        user = story
+            .user
+            .as_deref()
+            .map(|user| format!(" by {}", user))
+            .unwrap_or_default(),
+        comments_url = escape(&story.comments_url()),
+        date = story.date.to_rfc3339(),
+        date_displayed = story.date_displayed,
+        comment_count = story.comment_count.unwrap_or(0),
+    );
+    if let Some(html_content) = details.html_content.as_deref() {
+        let _ = writeln!(html, "<div class=\"text\">{}</div>", html_content);
+    }
+    let top_level = details.top_level();
+    if !top_level.is_empty() {
+        html.push_str("<section class=\"comments\">\n");
+        format_comments_html(&mut html, top_level, 0);
+        html.push_str("</section>\n");
+    }
+    if details.truncated {
+        html.push_str("<p class=\"truncated\">Some comments were not loaded, see the story on HackerNews to read them all.</p>\n");
+    }
+    html.push_str("</article>\n</body>\n</html>\n");
+    html
+}
+
+fn format_comments_html(html: &mut String, comments: &[Rc<Comment>], depth: usize) {
+    html.push_str("<ul>\n");
+    for comment in comments {
+        let _ = write!(
+            html,
+            r#"<li class="comment depth-{depth}" id="{id}">
+<p class="meta">{user} <a href="https://news.ycombinator.com/item?id={id}"><time datetime="{date}">{date_displayed}</time></a></p>
+<div class="text">{content}</div>
+"#,
+            depth = depth,
+            id = comment.id,
+            user = comment.user,
+            date = comment.date.to_rfc3339(),
+            date_displayed = comment.date_displayed,
+            content = comment.html_content,
+        );
+        let children = comment.children();
+        if !children.is_empty() {
+            format_comments_html(html, &children, depth + 1);
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ul>\n");
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use hnapi::{Story, StoryKind};
+    use url::Url;
+
+    #[test]
+    fn text_is_escaped() {
+        assert_eq!(
+            escape(r#"Show HN: <a> & "b""#),
+            "Show HN: &lt;a&gt; &amp; &quot;b&quot;".to_string()
+        );
+    }
+
+    #[test]
+    fn scraped_fields_are_not_escaped_again() {
+        let details = StoryWithDetails {
+            story: Story {
+                id: 29246573,
+                title: "Rust &amp; WebAssembly".to_string(),
+                url: Url::parse("https://example.com/?a=1&b=2").unwrap(),
+                url_displayed: Some("example.com".to_string()),
+                upvote_auth: None,
+                user: Some("someone".to_string()),
+                score: Some(42),
+                date: DateTime::parse_from_rfc3339("2021-11-17T10:00:00Z")
+                    .unwrap()
+                    .with_timezone(&Utc),
+                date_displayed: "2 hours ago".to_string(),
+                comment_count: Some(0),
+                kind: StoryKind::Regular,
+            },
+            html_content: None,
+            comments: vec![],
+            truncated: false,
+        };
+        let html = format_story_details_html(&details);
+        assert!(html.contains("<title>Rust &amp; WebAssembly</title>"));
+        assert!(!html.contains("&amp;amp;"));
+        assert!(html.contains(r#"href="https://example.com/?a=1&amp;b=2""#));
+    }
+}
@@ -1,23 +1,45 @@
-use crate::format::{format_comment, format_story, format_story_details, format_user};
-use crate::state::Auth;
+use crate::format::{
+    format_comment, format_hidden, format_story, format_story_change, format_story_details,
+    format_story_jsonl, format_trend, format_user, indent, DateFormat,
+};
+use crate::html::format_story_details_html;
+use crate::state::{Auth, Mutes, StoryPosition};
+use chrono::Utc;
 use clap::{self, crate_authors, crate_description, crate_name, crate_version, Arg, SubCommand};
-use console::style;
-use hnapi::{login, stories_list, story_details, user_details, Comment, Story, StoryList};
-use minus::Pager;
+use console::{style, Term};
+use hnapi::{
+    comment_thread, current_user, login, reading_time, stories_list, stories_since,
+    story_details_with_budget, story_reading_time, text_word_count, user_comments, user_details,
+    user_submissions, Comment, CommentFetchBudget, Story, StoryList, StoryWithDetails,
+};
+use pager::Pager;
+use rand::seq::IteratorRandom;
+use regex::Regex;
 use state::State;
 use std::fmt::Write as FmtWrite;
 use std::io::Write as IoWrite;
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet},
     error::Error,
     fs::{read_to_string, File},
+    time::{Duration, Instant},
 };
 
 mod format;
+mod html;
+mod pager;
 mod state;
+#[cfg(feature = "thumbnails")]
+mod thumbnail;
 
 extern crate reqwest;
 
+const MAX_COMMENT_PAGES: usize = 10;
+const COMMENTS_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_SEEN_STORIES: usize = 1000;
+const WATCH_INTERVAL: Duration = Duration::from_secs(60);
+const ARTICLE_TIMEOUT: Duration = Duration::from_secs(10);
+
 fn get_state_path() -> String {
     dirs::home_dir()
         .and_then(|home_dir| home_dir.to_str().map(ToString::to_string))
@@ -32,122 +54,424 @@ async fn main() -> Result<(), Box<dyn Error>> {
         .short("p")
         .takes_value(true)
         .help("Page number");
+    let fresh_arg = Arg::with_name("fresh")
+        .long("fresh")
+        .conflicts_with("page")
+        .help("Only show the stories of the first page not shown by a previous --fresh");
+    let watch_arg = Arg::with_name("watch")
+        .long("watch")
+        .short("w")
+        .conflicts_with_all(&["page", "fresh"])
+        .help("Keep checking the first page every minute and print the new stories");
+    let min_score_arg = Arg::with_name("min-score")
+        .long("min-score")
+        .takes_value(true)
+        .requires("watch")
+        .help("Only print the stories with at least this score when watching");
+    let notify_arg = Arg::with_name("notify")
+        .long("notify")
+        .requires("watch")
+        .help("Show a desktop notification for each new story when watching");
+    let diff_arg = Arg::with_name("diff")
+        .long("diff")
+        .conflicts_with_all(&["fresh", "watch"])
+        .help("Show what changed since the list was last shown: new stories, moves and scores");
+    let domain_arg = Arg::with_name("domain")
+        .long("domain")
+        .takes_value(true)
+        .help("Only show the stories from a domain (including its subdomains)");
+    let list_format_arg = Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["text", "jsonl"])
+        .default_value("text")
+        .help("Output format, jsonl to print each story as a line of JSON");
     let story_index_arg = Arg::with_name("INDEX").required(true).help("Story index");
-    let matches = clap::App::new(crate_name!())
-        .about(crate_description!())
-        .version(crate_version!())
-        .author(crate_authors!("\n"))
-        .subcommand(
-            SubCommand::with_name("top")
-                .alias("t")
-                .about("Print top stories (default command)")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("new")
-                .alias("n")
-                .about("Print new stories")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("best")
-                .alias("b")
-                .about("Print best stories")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("ask")
-                .alias("a")
-                .about("Print ask stories")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("show")
-                .alias("s")
-                .about("Print show stories")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("job")
-                .alias("j")
-                .about("Print best stories")
-                .arg(&page_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("details")
-                .alias("d")
-                .about("Print a story details")
-                .arg(&story_index_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("open")
-                .alias("o")
-                .about("Open a story’s link in the default browser")
-                .arg(&story_index_arg),
-        )
-        .subcommand(
-            SubCommand::with_name("user")
-                .alias("u")
-                .about("Show details about a user")
-                .arg(Arg::with_name("USER_NAME").required(true).help("User name")),
-        )
-        .subcommand(SubCommand::with_name("login").alias("l"))
-        .subcommand(SubCommand::with_name("logout"))
-        .subcommand(SubCommand::with_name("upvote").arg(&story_index_arg))
-        .get_matches();
+    let story_index_or_link_arg = Arg::with_name("INDEX")
+        .required(true)
+        .help("Story index, or link such as https://news.ycombinator.com/item?id=123");
+    let highlight_arg = Arg::with_name("highlight")
+        .long("highlight")
+        .takes_value(true)
+        .value_name("TERM")
+        .help("Highlight a word in the comments, whatever its case");
+    let highlight_regex_arg = Arg::with_name("highlight-regex")
+        .long("highlight-regex")
+        .takes_value(true)
+        .value_name("REGEX")
+        .conflicts_with("highlight")
+        .help("Highlight the matches of a regular expression in the comments");
+    let mute_user_arg = Arg::with_name("user")
+        .long("user")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("USER_NAME")
+        .help("User whose comments are collapsed");
+    let mute_domain_arg = Arg::with_name("domain")
+        .long("domain")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .value_name("DOMAIN")
+        .help("Domain whose stories are hidden, with its subdomains");
+    let no_pager_arg = Arg::with_name("no-pager")
+        .long("no-pager")
+        .help("Print the details directly instead of using a pager");
+    // The thumbnail is only printed without the pager, see `print_story_details`.
+    let details_no_pager_arg = no_pager_arg.clone().help(if cfg!(feature = "thumbnails") {
+        "Print the details directly instead of using a pager, with the thumbnail of the article"
+    } else {
+        "Print the details directly instead of using a pager"
+    });
+    let matches =
+        clap::App::new(crate_name!())
+            .about(crate_description!())
+            .version(crate_version!())
+            .author(crate_authors!("\n"))
+            .arg(
+                Arg::with_name("no-color")
+                    .long("no-color")
+                    .global(true)
+                    .help("Disable colors and styles"),
+            )
+            .arg(
+                Arg::with_name("date-format")
+                    .long("date-format")
+                    .global(true)
+                    .takes_value(true)
+                    .possible_values(&["relative", "iso", "unix"])
+                    .help("How dates are displayed (default: relative)"),
+            )
+            .subcommand(
+                SubCommand::with_name("top")
+                    .alias("t")
+                    .about("Print top stories (default command)")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("new")
+                    .alias("n")
+                    .about("Print new stories")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("best")
+                    .alias("b")
+                    .about("Print best stories")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("ask")
+                    .alias("a")
+                    .about("Print ask stories")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("show")
+                    .alias("s")
+                    .about("Print show stories")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("job")
+                    .alias("j")
+                    .about("Print best stories")
+                    .arg(&page_arg)
+                    .arg(&domain_arg)
+                    .arg(&fresh_arg)
+                    .arg(&diff_arg)
+                    .arg(&watch_arg)
+                    .arg(&min_score_arg)
+                    .arg(&notify_arg)
+                    .arg(&list_format_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("details")
+                    .alias("d")
+                    .about("Print a story details")
+                    .arg(&story_index_or_link_arg)
+                    .arg(&details_no_pager_arg)
+                    .arg(&highlight_arg)
+                    .arg(&highlight_regex_arg)
+                    .arg(
+                        Arg::with_name("format")
+                            .long("format")
+                            .takes_value(true)
+                            .possible_values(&["text", "html"])
+                            .default_value("text")
+                            .help("Output format, html to archive the discussion"),
+                    )
+                    .arg(
+                        Arg::with_name("reading-time")
+                            .long("reading-time")
+                            .help("Estimate the time needed to read the article (fetches it)"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("thread")
+                    .about("Print a comment and its replies")
+                    .arg(Arg::with_name("COMMENT").required(true).help(
+                        "Comment ID, or link such as https://news.ycombinator.com/item?id=123",
+                    ))
+                    .arg(&no_pager_arg)
+                    .arg(&highlight_arg)
+                    .arg(&highlight_regex_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("random")
+                    .about("Print the details of a random story from the front page"),
+            )
+            .subcommand(
+                SubCommand::with_name("open")
+                    .alias("o")
+                    .about("Open a story’s link in the default browser")
+                    .arg(&story_index_or_link_arg)
+                    .arg(
+                        Arg::with_name("clean")
+                            .long("clean")
+                            .help("Remove the tracking parameters (e.g. utm_source) from the link"),
+                    )
+                    .arg(
+                        Arg::with_name("both")
+                            .long("both")
+                            .help("Also open the discussion on HackerNews"),
+                    ),
+            )
+            .subcommand(
+                SubCommand::with_name("trend")
+                    .about("Print how a story’s rank and score changed across the lists shown")
+                    .arg(&story_index_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("user")
+                    .alias("u")
+                    .about("Show details about a user")
+                    .arg(Arg::with_name("USER_NAME").required(true).help("User name"))
+                    .arg(
+                        Arg::with_name("submissions")
+                            .long("submissions")
+                            .short("s")
+                            .conflicts_with("comments")
+                            .help("List the stories submitted by the user"),
+                    )
+                    .arg(
+                        Arg::with_name("comments")
+                            .long("comments")
+                            .short("c")
+                            .help("List the comments posted by the user"),
+                    )
+                    .arg(&highlight_arg)
+                    .arg(&highlight_regex_arg),
+            )
+            .subcommand(SubCommand::with_name("login").alias("l"))
+            .subcommand(SubCommand::with_name("logout"))
+            .subcommand(SubCommand::with_name("whoami").about("Show the user you are signed in as"))
+            .subcommand(SubCommand::with_name("upvote").arg(&story_index_arg))
+            .subcommand(
+                SubCommand::with_name("mute")
+                    .about("Hide the stories from a domain or the comments of a user, or list the muted ones")
+                    .arg(&mute_user_arg)
+                    .arg(&mute_domain_arg),
+            )
+            .subcommand(
+                SubCommand::with_name("unmute")
+                    .about("Show again the stories from a domain or the comments of a user")
+                    .arg(&mute_user_arg)
+                    .arg(&mute_domain_arg),
+            )
+            .get_matches();
+
+    if is_flag_present(&matches, "no-color") {
+        console::set_colors_enabled(false);
+    }
+
+    let date_format = match global_value(&matches, "date-format") {
+        Some("iso") => DateFormat::Iso,
+        Some("unix") => DateFormat::Unix,
+        _ => DateFormat::Relative,
+    };
 
     let state_path = get_state_path();
     let mut state = read_state(&state_path);
     let token = state.auth.as_ref().map(|auth| auth.token.clone());
     match matches.subcommand() {
-        ("" | "top", matches) => {
-            let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::News, page, state.last_stories, &token).await?);
-            save_state(&state, &state_path)?;
-        }
-        ("new", matches) => {
+        (name @ ("" | "top" | "new" | "best" | "ask" | "show" | "job"), matches) => {
+            let list = match name {
+                "new" => StoryList::Newest,
+                "best" => StoryList::Best,
+                "ask" => StoryList::Ask,
+                "show" => StoryList::Show,
+                "job" => StoryList::Jobs,
+                _ => StoryList::News,
+            };
             let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::Newest, page, state.last_stories, &token).await?);
-            save_state(&state, &state_path)?;
-        }
-        ("best", matches) => {
-            let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::Best, page, state.last_stories, &token).await?);
+            let domain = get_domain_from_matches(matches);
+            let format = get_list_format_from_matches(matches);
+            let list_name = if name.is_empty() { "top" } else { name };
+            if matches.is_some_and(|matches| matches.is_present("watch")) {
+                let min_score = get_min_score_from_matches(matches);
+                let notify = matches.is_some_and(|matches| matches.is_present("notify"));
+                let options = WatchOptions {
+                    domain,
+                    min_score,
+                    notify,
+                    format,
+                    date_format,
+                };
+                return watch_stories(list, &options, &token, &mut state, &state_path).await;
+            }
+            let diff = format == ListFormat::Text
+                && matches.is_some_and(|matches| matches.is_present("diff"));
+            let (stories, previous) = if matches.is_some_and(|matches| matches.is_present("fresh"))
+            {
+                let seen_ids = state.seen_stories.entry(list_name.to_string()).or_default();
+                let stories = stories_since(list, seen_ids, &token).await?;
+                remember_seen_stories(seen_ids, &stories);
+                (stories, None)
+            } else {
+                let stories = stories_list(list, page, &token).await?;
+                let previous = state
+                    .last_positions
+                    .insert(format!("{}:{}", list_name, page), story_positions(&stories));
+                (stories, previous)
+            };
+            state.record_snapshots(&stories, Utc::now());
+            let last_stories = state.last_stories.take();
+            state.last_stories = Some(if diff {
+                let previous = previous.unwrap_or_default();
+                print_story_diff(
+                    stories,
+                    domain,
+                    &previous,
+                    date_format,
+                    &state.mutes,
+                    last_stories,
+                )
+            } else {
+                print_stories(
+                    stories,
+                    domain,
+                    format,
+                    date_format,
+                    &state.mutes,
+                    last_stories,
+                )
+            });
             save_state(&state, &state_path)?;
         }
-        ("ask", matches) => {
-            let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::Ask, page, state.last_stories, &token).await?);
-            save_state(&state, &state_path)?;
+        ("details", matches) => {
+            let id = get_linked_id_from_matches(matches)
+                .or_else(|| get_story_from_matches(matches, &state).map(|story| story.id));
+            if let Some(id) = id {
+                let matches = matches.unwrap();
+                if matches.value_of("format") == Some("html") {
+                    print_story_details_html(id).await?;
+                } else {
+                    let use_pager = !matches.is_present("no-pager") && Term::stdout().is_term();
+                    let reading_time = matches.is_present("reading-time");
+                    let highlight = get_highlight_from_matches(matches)?;
+                    print_story_details(
+                        id,
+                        use_pager,
+                        date_format,
+                        reading_time,
+                        highlight.as_ref(),
+                        &state.mutes,
+                    )
+                    .await?;
+                }
+            } else {
+                eprintln!("Invalid story index.")
+            }
         }
-        ("show", matches) => {
-            let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::Show, page, state.last_stories, &token).await?);
-            save_state(&state, &state_path)?;
+        ("thread", matches) => {
+            let matches = matches.unwrap();
+            if let Some(id) = matches.value_of("COMMENT").and_then(parse_item_id) {
+                let use_pager = !matches.is_present("no-pager") && Term::stdout().is_term();
+                let highlight = get_highlight_from_matches(matches)?;
+                print_comment_thread(id, use_pager, date_format, highlight.as_ref(), &state.mutes)
+                    .await?;
+            } else {
+                eprintln!("Invalid comment ID or link.")
+            }
         }
-        ("job", matches) => {
-            let page = get_page_from_matches(matches);
-            state.last_stories =
-                Some(print_stories(StoryList::Jobs, page, state.last_stories, &token).await?);
-            save_state(&state, &state_path)?;
+        ("random", _) => {
+            let stories = stories_list(StoryList::News, 1, &token).await?;
+            let story = stories
+                .values()
+                .filter(|story| !state.mutes.is_story_muted(story))
+                .choose(&mut rand::thread_rng());
+            if let Some(story) = story {
+                print_story_details(
+                    story.id,
+                    Term::stdout().is_term(),
+                    date_format,
+                    false,
+                    None,
+                    &state.mutes,
+                )
+                .await?;
+            } else {
+                eprintln!("No stories found.")
+            }
         }
-        ("details", matches) => {
-            let last_story = get_story_from_matches(matches, &state);
-            if let Some(last_story) = last_story {
-                print_story_details(last_story.id).await?;
+        ("open", matches) => {
+            let clean = matches.is_some_and(|matches| matches.is_present("clean"));
+            let both = matches.is_some_and(|matches| matches.is_present("both"));
+            if let Some(id) = get_linked_id_from_matches(matches) {
+                match fetch_story(id).await? {
+                    Some(story) => open_story_link(&story, clean, both).await?,
+                    None => eprintln!("No story found with this ID."),
+                }
+            } else if let Some(last_story) = get_story_from_matches(matches, &state) {
+                open_story_link(last_story, clean, both).await?;
             } else {
                 eprintln!("Invalid story index.")
             }
         }
-        ("open", matches) => {
+        ("trend", matches) => {
             let last_story = get_story_from_matches(matches, &state);
             if let Some(last_story) = last_story {
-                open_story_link(last_story).await?;
+                match state.snapshots.get(&last_story.id) {
+                    Some(snapshots) => println!("{}", format_trend(snapshots, date_format)),
+                    None => eprintln!("No history for this story yet."),
+                }
             } else {
                 eprintln!("Invalid story index.")
             }
@@ -185,10 +509,26 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
         }
         ("user", matches) => {
-            let user_id = matches
-                .and_then(|matches| matches.value_of("USER_NAME"))
-                .unwrap();
-            if let Some(user) = user_details(user_id).await? {
+            let matches = matches.unwrap();
+            let user_id = matches.value_of("USER_NAME").unwrap();
+            if matches.is_present("submissions") {
+                let stories = user_submissions(user_id, &token).await?;
+                state.last_stories = Some(print_story_map(
+                    stories,
+                    ListFormat::Text,
+                    date_format,
+                    state.last_stories,
+                ));
+                save_state(&state, &state_path)?;
+            } else if matches.is_present("comments") {
+                let highlight = get_highlight_from_matches(matches)?;
+                for comment in user_comments(user_id).await? {
+                    println!(
+                        "\n{}",
+                        format_comment(&comment, 0, date_format, highlight.as_ref())
+                    );
+                }
+            } else if let Some(user) = user_details(user_id).await? {
                 println!("{}", format_user(&user));
             } else {
                 eprintln!("Invalid user name.")
@@ -200,16 +540,30 @@ async fn main() -> Result<(), Box<dyn Error>> {
             } else {
                 let username = prompt("Username: ")?;
                 let password = prompt("Password: ")?;
-                let token = login(&username, &password).await?;
-                if let Some((token, expires)) = token {
+                if let Some(auth) = login(&username, &password).await? {
                     println!("Successfully signed in as {}.", style(&username).bold());
-                    state.auth = Some(Auth::new(&username, &token, &expires));
+                    state.auth = Some(Auth::new(&username, &auth.token(), &auth.expires));
                     save_state(&state, &state_path)?;
                 } else {
                     println!("Invalid username or password.");
                 }
             }
         }
+        ("whoami", _) => {
+            if let Some(auth) = state.auth.as_ref() {
+                if let Some(username) = current_user(&auth.token).await? {
+                    println!("Signed in as {}.", style(&username).bold());
+                } else {
+                    eprintln!(
+                        "Your session has expired.\nPlease {} and {} again.",
+                        style("logout").bold(),
+                        style("login").bold()
+                    );
+                }
+            } else {
+                println!("Not signed in.");
+            }
+        }
         ("logout", _) => {
             if state.auth.is_some() {
                 state.auth = None;
@@ -219,6 +573,30 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 println!("Not signed in.");
             }
         }
+        ("mute", matches) => {
+            let matches = matches.unwrap();
+            let mutes = &mut state.mutes;
+            mutes
+                .users
+                .extend(values(matches, "user").map(str::to_string));
+            mutes
+                .domains
+                .extend(values(matches, "domain").map(normalize_domain));
+            save_state(&state, &state_path)?;
+            print_mutes(&state.mutes);
+        }
+        ("unmute", matches) => {
+            let matches = matches.unwrap();
+            let mutes = &mut state.mutes;
+            for user in values(matches, "user") {
+                mutes.users.remove(user);
+            }
+            for domain in values(matches, "domain") {
+                mutes.domains.remove(&normalize_domain(domain));
+            }
+            save_state(&state, &state_path)?;
+            print_mutes(&state.mutes);
+        }
         _ => (),
     };
 
@@ -236,6 +614,24 @@ fn prompt(prompt: &str) -> Result<String, std::io::Error> {
     Ok(input.trim_end().to_string())
 }
 
+/// Whether a global flag is present, either before or after the subcommand.
+fn is_flag_present(matches: &clap::ArgMatches, name: &str) -> bool {
+    matches.is_present(name)
+        || matches
+            .subcommand()
+            .1
+            .is_some_and(|matches| matches.is_present(name))
+}
+
+/// Value of a global argument, given either before or after the subcommand.
+fn global_value<'a>(matches: &'a clap::ArgMatches, name: &str) -> Option<&'a str> {
+    matches
+        .subcommand()
+        .1
+        .and_then(|matches| matches.value_of(name))
+        .or_else(|| matches.value_of(name))
+}
+
 fn get_page_from_matches(matches: Option<&clap::ArgMatches>) -> usize {
     matches
         .and_then(|matches| matches.value_of("page"))
@@ -243,6 +639,24 @@ fn get_page_from_matches(matches: Option<&clap::ArgMatches>) -> usize {
         .unwrap_or(1)
 }
 
+fn get_domain_from_matches<'a>(matches: Option<&'a clap::ArgMatches>) -> Option<&'a str> {
+    matches.and_then(|matches| matches.value_of("domain"))
+}
+
+fn get_list_format_from_matches(matches: Option<&clap::ArgMatches>) -> ListFormat {
+    match matches.and_then(|matches| matches.value_of("format")) {
+        Some("jsonl") => ListFormat::Jsonl,
+        _ => ListFormat::Text,
+    }
+}
+
+fn get_min_score_from_matches(matches: Option<&clap::ArgMatches>) -> u32 {
+    matches
+        .and_then(|matches| matches.value_of("min-score"))
+        .and_then(|min_score| min_score.parse().ok())
+        .unwrap_or(0)
+}
+
 fn get_story_from_matches<'a>(
     matches: Option<&clap::ArgMatches>,
     state: &'a State,
@@ -253,62 +667,442 @@ fn get_story_from_matches<'a>(
         .and_then(|index| state.get_last_story(index))
 }
 
+/// Pattern to highlight in the comments: the `--highlight` term as a whole word whatever its
+/// case, or the `--highlight-regex` pattern as is.
+fn get_highlight_from_matches(matches: &clap::ArgMatches) -> Result<Option<Regex>, regex::Error> {
+    if let Some(term) = matches.value_of("highlight") {
+        Regex::new(&format!(r"(?i)\b{}\b", regex::escape(term))).map(Some)
+    } else if let Some(pattern) = matches.value_of("highlight-regex") {
+        Regex::new(pattern).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// ID of the story when it is given with a link instead of its index.
+fn get_linked_id_from_matches(matches: Option<&clap::ArgMatches>) -> Option<u32> {
+    matches
+        .and_then(|matches| matches.value_of("INDEX"))
+        .and_then(item_link_id)
+}
+
 fn result_to_option<T, E>(result: Result<T, E>) -> Option<T> {
     result.map(Some).unwrap_or(None)
 }
 
-async fn print_stories(
-    list: StoryList,
-    page: usize,
+/// How lists of stories are printed.
+#[derive(Clone, Copy, PartialEq)]
+enum ListFormat {
+    /// For humans, with colors.
+    Text,
+    /// One JSON object per story and per line, e.g. to export stories.
+    Jsonl,
+}
+
+fn print_stories(
+    mut stories: HashMap<usize, Story>,
+    domain: Option<&str>,
+    format: ListFormat,
+    date_format: DateFormat,
+    mutes: &Mutes,
     last_stories: Option<HashMap<usize, Story>>,
+) -> HashMap<usize, Story> {
+    if let Some(domain) = domain {
+        stories.retain(|_, story| story.matches_domain(domain));
+    }
+    let hidden = hide_muted_stories(&mut stories, mutes);
+    let last_stories = print_story_map(stories, format, date_format, last_stories);
+    if hidden > 0 && format == ListFormat::Text {
+        println!("{}", format_hidden(hidden));
+    }
+    last_stories
+}
+
+/// Remove the stories from muted domains, returning how many were removed.
+fn hide_muted_stories(stories: &mut HashMap<usize, Story>, mutes: &Mutes) -> usize {
+    let count = stories.len();
+    stories.retain(|_, story| !mutes.is_story_muted(story));
+    count - stories.len()
+}
+
+fn story_positions(stories: &HashMap<usize, Story>) -> HashMap<u32, StoryPosition> {
+    stories
+        .iter()
+        .map(|(&rank, story)| {
+            let position = StoryPosition {
+                rank,
+                score: story.score,
+            };
+            (story.id, position)
+        })
+        .collect()
+}
+
+/// Print the stories with what changed for each of them since the previous positions.
+fn print_story_diff(
+    mut stories: HashMap<usize, Story>,
+    domain: Option<&str>,
+    previous: &HashMap<u32, StoryPosition>,
+    date_format: DateFormat,
+    mutes: &Mutes,
+    last_stories: Option<HashMap<usize, Story>>,
+) -> HashMap<usize, Story> {
+    if let Some(domain) = domain {
+        stories.retain(|_, story| story.matches_domain(domain));
+    }
+    let hidden = hide_muted_stories(&mut stories, mutes);
+    let mut last_stories = last_stories.unwrap_or_default();
+    let mut ranks: Vec<usize> = stories.keys().copied().collect();
+    ranks.sort_unstable();
+    for rank in ranks {
+        let story = stories.get(&rank).unwrap();
+        println!(
+            "{}  {}",
+            format_story(rank, story, date_format),
+            format_story_change(rank, story, previous.get(&story.id))
+        );
+    }
+    if hidden > 0 {
+        println!("{}", format_hidden(hidden));
+    }
+    last_stories.extend(stories);
+    last_stories
+}
+
+struct WatchOptions<'a> {
+    domain: Option<&'a str>,
+    min_score: u32,
+    notify: bool,
+    format: ListFormat,
+    date_format: DateFormat,
+}
+
+/// Print the stories appearing on the first page of a list, checking it regularly until
+/// interrupted. Only the stories with at least `min_score` points are printed, so a
+/// story already in the list is printed once it reaches that score.
+async fn watch_stories(
+    list: StoryList,
+    options: &WatchOptions<'_>,
     token: &Option<String>,
-) -> Result<HashMap<usize, Story>, Box<dyn Error>> {
-    let stories = stories_list(list, page, token).await?;
+    state: &mut State,
+    state_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut shown_ids = HashSet::new();
+    let mut first_check = true;
+    loop {
+        // A failed check is retried at the next one rather than ending the watch.
+        let mut stories = match stories_since(list, &shown_ids, token).await {
+            Ok(stories) => stories,
+            Err(err) => {
+                eprintln!("Unable to check the new stories: {}", err);
+                tokio::time::sleep(WATCH_INTERVAL).await;
+                continue;
+            }
+        };
+        // Muted stories are counted once, then considered as shown.
+        shown_ids.extend(
+            stories
+                .values()
+                .filter(|story| state.mutes.is_story_muted(story))
+                .map(|story| story.id),
+        );
+        let hidden = hide_muted_stories(&mut stories, &state.mutes);
+        stories.retain(|_, story| {
+            story.score.unwrap_or(0) >= options.min_score
+                && options
+                    .domain
+                    .is_none_or(|domain| story.matches_domain(domain))
+        });
+        shown_ids.extend(stories.values().map(|story| story.id));
+        state.record_snapshots(&stories, Utc::now());
+        // The stories already there when starting are printed but not notified.
+        if options.notify && !first_check {
+            for story in stories.values() {
+                notify_story(story);
+            }
+        }
+        state.last_stories = Some(print_story_map(
+            stories,
+            options.format,
+            options.date_format,
+            state.last_stories.take(),
+        ));
+        if hidden > 0 && options.format == ListFormat::Text {
+            println!("{}", format_hidden(hidden));
+        }
+        save_state(state, state_path)?;
+        first_check = false;
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
+/// Show a desktop notification for a story, with its link. Where notifications are not
+/// supported, the story is printed on the standard error instead.
+fn notify_story(story: &Story) {
+    let notification = notify_rust::Notification::new()
+        .appname("hn")
+        .summary(&story.title)
+        .body(story.url.as_str())
+        .show();
+    if let Err(err) = notification {
+        eprintln!(
+            "New story: {} ({}). Unable to show a notification: {}",
+            story.title, story.url, err
+        );
+    }
+}
+
+/// Add the stories to the seen ones, keeping only the most recent when there are too
+/// many of them (story IDs are increasing).
+fn remember_seen_stories(seen_ids: &mut HashSet<u32>, stories: &HashMap<usize, Story>) {
+    seen_ids.extend(stories.values().map(|story| story.id));
+    if seen_ids.len() > MAX_SEEN_STORIES {
+        let mut ids: Vec<u32> = seen_ids.drain().collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        seen_ids.extend(ids.into_iter().take(MAX_SEEN_STORIES));
+    }
+}
+
+fn print_story_map(
+    stories: HashMap<usize, Story>,
+    format: ListFormat,
+    date_format: DateFormat,
+    last_stories: Option<HashMap<usize, Story>>,
+) -> HashMap<usize, Story> {
     let mut last_stories = last_stories.unwrap_or_default();
     let mut ranks: Vec<usize> = stories.keys().copied().collect();
     ranks.sort_unstable();
     for rank in ranks {
         let story = stories.get(&rank).unwrap();
-        println!("{}", format_story(rank, story));
+        match format {
+            ListFormat::Text => println!("{}", format_story(rank, story, date_format)),
+            ListFormat::Jsonl => println!("{}", format_story_jsonl(rank, story)),
+        }
     }
     last_stories.extend(stories);
-    Ok(last_stories)
+    last_stories
+}
+
+/// Story with a given ID and its comments. `None` if the item doesn’t exist or isn’t a
+/// story, e.g. when given the link of a comment.
+async fn fetch_story_details(id: u32) -> Result<Option<StoryWithDetails>, Box<dyn Error>> {
+    let budget = CommentFetchBudget {
+        max_pages: Some(MAX_COMMENT_PAGES),
+        max_comments: None,
+        deadline: Some(Instant::now() + COMMENTS_TIMEOUT),
+    };
+    Ok(story_details_with_budget(id, &budget).await?)
+}
+
+/// Story with a given ID, without its comments.
+async fn fetch_story(id: u32) -> Result<Option<Story>, Box<dyn Error>> {
+    let budget = CommentFetchBudget {
+        max_pages: Some(1),
+        max_comments: Some(0),
+        deadline: None,
+    };
+    let details = story_details_with_budget(id, &budget).await?;
+    Ok(details.map(|details| details.story))
+}
+
+async fn print_story_details_html(id: u32) -> Result<(), Box<dyn Error>> {
+    let details = match fetch_story_details(id).await? {
+        Some(details) => details,
+        None => {
+            eprintln!("No story found with this ID.");
+            return Ok(());
+        }
+    };
+    print!("{}", format_story_details_html(&details));
+    Ok(())
 }
 
-async fn print_story_details(id: u32) -> Result<(), Box<dyn Error>> {
-    let mut output = Pager::new().unwrap();
-    output.set_prompt("More");
+async fn print_story_details(
+    id: u32,
+    use_pager: bool,
+    date_format: DateFormat,
+    reading_time: bool,
+    highlight: Option<&Regex>,
+    mutes: &Mutes,
+) -> Result<(), Box<dyn Error>> {
+    let details = match fetch_story_details(id).await? {
+        Some(details) => details,
+        None => {
+            eprintln!("No story found with this ID.");
+            return Ok(());
+        }
+    };
+    let reading_time = if reading_time {
+        estimate_reading_time(&details).await
+    } else {
+        None
+    };
+
+    // Images can't go through the pager, and the thumbnail is only a bonus: the details are
+    // printed even if it fails.
+    #[cfg(feature = "thumbnails")]
+    if !use_pager && !details.story.is_text_post() && thumbnail::is_supported() {
+        let _ = thumbnail::print_thumbnail(&details.story.url).await;
+    }
+
+    let mut output = String::new();
+    writeln!(
+        output,
+        "{}",
+        format_story_details(&details, date_format, reading_time, highlight)
+    )?;
 
-    let details = story_details(id).await?.unwrap();
-    writeln!(output, "{}", format_story_details(&details))?;
+    for comment in details.top_level() {
+        print_comment(&mut output, comment, 0, date_format, highlight, mutes)?;
+    }
 
-    let comments = details.comments;
-    for comment in comments {
-        print_comment(&mut output, &comment, 0)?;
+    if details.truncated {
+        writeln!(
+            output,
+            "\n{}",
+            style("Some comments were not loaded, see the story on HackerNews to read them all.")
+                .dim()
+        )?;
     }
 
-    minus::page_all(output)?;
+    show_output(&output, use_pager)
+}
 
+/// Reading time of the story in minutes, from its own text for text posts, or from the
+/// article it links to. `None` if the article can't be fetched.
+async fn estimate_reading_time(details: &StoryWithDetails) -> Option<u32> {
+    if details.story.is_text_post() {
+        let text = details.html_content.as_deref().unwrap_or_default();
+        return Some(reading_time(text_word_count(text)));
+    }
+    let minutes = tokio::time::timeout(ARTICLE_TIMEOUT, story_reading_time(&details.story)).await;
+    minutes.ok()?.ok()?
+}
+
+async fn print_comment_thread(
+    id: u32,
+    use_pager: bool,
+    date_format: DateFormat,
+    highlight: Option<&Regex>,
+    mutes: &Mutes,
+) -> Result<(), Box<dyn Error>> {
+    let comment = match comment_thread(id).await? {
+        Some(comment) => comment,
+        None => {
+            eprintln!("No comment found with this ID.");
+            return Ok(());
+        }
+    };
+    let mut output = String::new();
+    print_comment(&mut output, &comment, 0, date_format, highlight, mutes)?;
+    show_output(output.trim_start(), use_pager)
+}
+
+fn show_output(output: &str, use_pager: bool) -> Result<(), Box<dyn Error>> {
+    if use_pager {
+        Pager::from_env().page(output)?;
+    } else {
+        print!("{}", output);
+    }
     Ok(())
 }
 
-fn print_comment<'a>(
-    output: &'a mut Pager,
-    comment: &'a Comment,
+/// ID of an item (story or comment) given as is, or with a link to its page, e.g.
+/// `https://news.ycombinator.com/item?id=123`.
+fn parse_item_id(arg: &str) -> Option<u32> {
+    arg.trim().parse().ok().or_else(|| item_link_id(arg))
+}
+
+/// ID of an item given with a link to its page on HackerNews.
+fn item_link_id(arg: &str) -> Option<u32> {
+    let url = url::Url::parse(arg.trim()).ok()?;
+    if url.host_str() != Some("news.ycombinator.com") || url.path() != "/item" {
+        return None;
+    }
+    url.query_pairs()
+        .find(|(key, _)| key == "id")
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+fn print_comment(
+    output: &mut String,
+    comment: &Comment,
     level: usize,
+    date_format: DateFormat,
+    highlight: Option<&Regex>,
+    mutes: &Mutes,
 ) -> Result<(), Box<dyn Error>> {
-    writeln!(output, "\n{}", format_comment(comment, level))?;
-    let children = comment.children.borrow();
-    for child_comment in children.iter() {
-        print_comment(output, child_comment, level + 1)?;
+    // Comments of muted users are collapsed with their replies.
+    if mutes.is_user_muted(&comment.user) {
+        let hidden = format_hidden(count_comments(comment));
+        writeln!(output, "\n{}", indent(&hidden, level))?;
+        return Ok(());
+    }
+    writeln!(
+        output,
+        "\n{}",
+        format_comment(comment, level, date_format, highlight)
+    )?;
+    for child_comment in comment.children() {
+        print_comment(
+            output,
+            &child_comment,
+            level + 1,
+            date_format,
+            highlight,
+            mutes,
+        )?;
     }
 
     Ok(())
 }
 
-async fn open_story_link(story: &Story) -> Result<(), Box<dyn Error>> {
-    if webbrowser::open(story.url.as_str()).is_err() {
-        eprintln!("Error while opening the default browser.");
+/// Number of comments in a thread, counting the comment itself and all the replies.
+fn count_comments(comment: &Comment) -> usize {
+    1 + comment
+        .children()
+        .iter()
+        .map(|child| count_comments(child))
+        .sum::<usize>()
+}
+
+fn print_mutes(mutes: &Mutes) {
+    let list = |values: &BTreeSet<String>| {
+        if values.is_empty() {
+            style("none").dim().to_string()
+        } else {
+            values.iter().cloned().collect::<Vec<String>>().join(", ")
+        }
+    };
+    println!("Muted users: {}", list(&mutes.users));
+    println!("Muted domains: {}", list(&mutes.domains));
+}
+
+fn normalize_domain(domain: &str) -> String {
+    domain.trim().trim_start_matches("www.").to_lowercase()
+}
+
+fn values<'a>(matches: &'a clap::ArgMatches, name: &str) -> impl Iterator<Item = &'a str> {
+    matches.values_of(name).into_iter().flatten()
+}
+
+/// Open the story link, and with `both` its discussion too. The link of a text post is
+/// already its discussion, so it is opened only once.
+async fn open_story_link(story: &Story, clean: bool, both: bool) -> Result<(), Box<dyn Error>> {
+    let url = if clean {
+        story.clean_url()
+    } else {
+        story.url.clone()
+    };
+    let mut urls = vec![url.to_string()];
+    if both && !story.is_text_post() {
+        urls.push(story.comments_url());
+    }
+    for url in urls {
+        if webbrowser::open(&url).is_err() {
+            eprintln!("Error while opening the default browser.");
+            break;
+        }
     }
     Ok(())
 }
@@ -331,3 +1125,25 @@ fn save_state(state: &State, state_path: &str) -> Result<(), std::io::Error> {
     write!(&mut file, "{}", serde_json::to_string(state).unwrap())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn item_ids_are_read_from_ids_and_links() {
+        assert_eq!(parse_item_id("29210341"), Some(29210341));
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/item?id=29210341"),
+            Some(29210341)
+        );
+        assert_eq!(parse_item_id("https://example.com/"), None);
+        assert_eq!(parse_item_id("https://github.com/x?id=3"), None);
+        assert_eq!(
+            parse_item_id("https://news.ycombinator.com/user?id=3"),
+            None
+        );
+        assert_eq!(parse_item_id("five"), None);
+        assert_eq!(item_link_id("5"), None);
+    }
+}
@@ -0,0 +1,124 @@
+use minus::Pager as MinusPager;
+use std::env;
+use std::error::Error;
+use std::fmt::Write as FmtWrite;
+use std::io::Write as IoWrite;
+use std::process::{Command, Stdio};
+
+const DEFAULT_PROMPT: &str = "More";
+
+/// The pager used to display long outputs, such as story details.
+#[derive(Debug, PartialEq)]
+pub enum Pager {
+    /// The pager built in the CLI, displaying the given prompt.
+    Builtin { prompt: String },
+    /// An external command (e.g. `less -R`), receiving the output on its standard input.
+    External(String),
+}
+
+impl Pager {
+    /// Read the pager from the environment: `HN_PAGER` if set, `PAGER` otherwise. The
+    /// built-in pager is used when none is set, or when it is set to `minus`; its prompt
+    /// can be changed with `HN_PAGER_PROMPT`.
+    pub fn from_env() -> Pager {
+        Pager::from_values(
+            env::var("HN_PAGER").ok(),
+            env::var("PAGER").ok(),
+            env::var("HN_PAGER_PROMPT").ok(),
+        )
+    }
+
+    fn from_values(
+        hn_pager: Option<String>,
+        pager: Option<String>,
+        prompt: Option<String>,
+    ) -> Pager {
+        match hn_pager.or(pager) {
+            Some(command) if !command.trim().is_empty() && command.trim() != "minus" => {
+                Pager::External(command.trim().to_string())
+            }
+            _ => Pager::Builtin {
+                prompt: prompt.unwrap_or_else(|| DEFAULT_PROMPT.to_string()),
+            },
+        }
+    }
+
+    pub fn page(&self, output: &str) -> Result<(), Box<dyn Error>> {
+        match self {
+            Pager::Builtin { prompt } => {
+                let mut pager = MinusPager::new().unwrap();
+                pager.set_prompt(prompt);
+                write!(pager, "{}", output)?;
+                minus::page_all(pager)?;
+            }
+            Pager::External(command) => {
+                let mut child = shell_command(command)
+                    // Like git, let `less` display colors and exit if the output fits
+                    // on one screen, unless the user configured it otherwise.
+                    .env(
+                        "LESS",
+                        env::var("LESS").unwrap_or_else(|_| "FRX".to_string()),
+                    )
+                    .stdin(Stdio::piped())
+                    .spawn()?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    // The pager may be quit before reading everything, which is fine.
+                    let _ = stdin.write_all(output.as_bytes());
+                }
+                child.wait()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell_command(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hn_pager_takes_precedence_over_pager() {
+        assert_eq!(
+            Pager::from_values(Some("less -R".to_string()), Some("more".to_string()), None),
+            Pager::External("less -R".to_string())
+        );
+        assert_eq!(
+            Pager::from_values(None, Some("more".to_string()), None),
+            Pager::External("more".to_string())
+        );
+    }
+
+    #[test]
+    fn builtin_pager_is_used_by_default() {
+        assert_eq!(
+            Pager::from_values(None, None, None),
+            Pager::Builtin {
+                prompt: "More".to_string()
+            }
+        );
+        assert_eq!(
+            Pager::from_values(
+                Some("minus".to_string()),
+                Some("less".to_string()),
+                Some("Next".to_string())
+            ),
+            Pager::Builtin {
+                prompt: "Next".to_string()
+            }
+        );
+    }
+}
@@ -1,18 +1,23 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use chrono::{DateTime, Utc};
 use hnapi::Story;
 use serde::{Deserialize, Serialize};
 
+/// Number of snapshots kept for each story.
+const MAX_SNAPSHOTS_PER_STORY: usize = 20;
+/// Number of stories whose snapshots are kept, the most recent ones.
+const MAX_TRACKED_STORIES: usize = 200;
+
 #[derive(Serialize, Deserialize)]
 pub struct Auth {
     pub username: String,
     pub token: String,
-    pub expires: DateTime<Utc>,
+    pub expires: Option<DateTime<Utc>>,
 }
 
 impl Auth {
-    pub fn new(username: &str, token: &str, expires: &DateTime<Utc>) -> Auth {
+    pub fn new(username: &str, token: &str, expires: &Option<DateTime<Utc>>) -> Auth {
         Auth {
             username: username.to_string(),
             token: token.to_string(),
@@ -21,10 +26,58 @@ impl Auth {
     }
 }
 
+/// Rank and score of a story when its list was last shown.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct StoryPosition {
+    pub rank: usize,
+    pub score: Option<u32>,
+}
+
+/// Rank and score of a story at a given time, to show how it evolved with `hn trend`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub date: DateTime<Utc>,
+    pub rank: usize,
+    pub score: Option<u32>,
+}
+
+/// Users and domains muted with `hn mute`: stories from the domains are hidden from the
+/// lists, and comments from the users are collapsed.
+#[derive(Default, Serialize, Deserialize)]
+pub struct Mutes {
+    pub users: BTreeSet<String>,
+    pub domains: BTreeSet<String>,
+}
+
+impl Mutes {
+    /// Whether the story links to a muted domain or one of its subdomains.
+    pub fn is_story_muted(&self, story: &Story) -> bool {
+        self.domains
+            .iter()
+            .any(|domain| story.matches_domain(domain))
+    }
+
+    pub fn is_user_muted(&self, user: &str) -> bool {
+        self.users.contains(user)
+    }
+}
+
 #[derive(Default, Serialize, Deserialize)]
 pub struct State {
     pub last_stories: Option<HashMap<usize, Story>>,
     pub auth: Option<Auth>,
+    /// IDs of the stories already shown with `--fresh`, for each list.
+    #[serde(default)]
+    pub seen_stories: HashMap<String, HashSet<u32>>,
+    /// Position of the stories last shown for each page of each list (e.g. “top:2”), by
+    /// story ID, to show what changed with `--diff`.
+    #[serde(default)]
+    pub last_positions: HashMap<String, HashMap<u32, StoryPosition>>,
+    /// Last snapshots of the stories shown in lists, by story ID, oldest first.
+    #[serde(default)]
+    pub snapshots: HashMap<u32, Vec<Snapshot>>,
+    #[serde(default)]
+    pub mutes: Mutes,
 }
 
 impl State {
@@ -35,4 +88,84 @@ impl State {
             None
         }
     }
+
+    /// Record the rank and score of the stories, keeping only the last snapshots of
+    /// the most recent stories (story IDs are increasing) so the state file stays small.
+    pub fn record_snapshots(&mut self, stories: &HashMap<usize, Story>, date: DateTime<Utc>) {
+        for (&rank, story) in stories {
+            let snapshots = self.snapshots.entry(story.id).or_default();
+            snapshots.push(Snapshot {
+                date,
+                rank,
+                score: story.score,
+            });
+            if snapshots.len() > MAX_SNAPSHOTS_PER_STORY {
+                snapshots.drain(..snapshots.len() - MAX_SNAPSHOTS_PER_STORY);
+            }
+        }
+        if self.snapshots.len() > MAX_TRACKED_STORIES {
+            let mut ids: Vec<u32> = self.snapshots.keys().copied().collect();
+            ids.sort_unstable_by(|a, b| b.cmp(a));
+            for id in &ids[MAX_TRACKED_STORIES..] {
+                self.snapshots.remove(id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use hnapi::StoryKind;
+    use url::Url;
+
+    fn story(id: u32, score: u32) -> Story {
+        Story {
+            id,
+            title: "Rust".to_string(),
+            url: Url::parse("https://www.rust-lang.org/").unwrap(),
+            url_displayed: Some("rust-lang.org".to_string()),
+            upvote_auth: None,
+            user: Some("someone".to_string()),
+            score: Some(score),
+            date: Utc.timestamp(1637143200, 0),
+            date_displayed: "2 hours ago".to_string(),
+            comment_count: Some(3),
+            kind: StoryKind::Regular,
+        }
+    }
+
+    #[test]
+    fn snapshots_are_capped() {
+        let mut state = State::default();
+        for i in 0..(MAX_SNAPSHOTS_PER_STORY as u32 + 5) {
+            let stories = HashMap::from([(1, story(42, i))]);
+            state.record_snapshots(&stories, Utc.timestamp(1637143200 + i64::from(i), 0));
+        }
+        let snapshots = &state.snapshots[&42];
+        assert_eq!(snapshots.len(), MAX_SNAPSHOTS_PER_STORY);
+        assert_eq!(snapshots[0].score, Some(5));
+
+        let stories = (0..MAX_TRACKED_STORIES + 10)
+            .map(|i| (i + 1, story(100 + i as u32, 1)))
+            .collect();
+        state.record_snapshots(&stories, Utc.timestamp(1637150000, 0));
+        assert_eq!(state.snapshots.len(), MAX_TRACKED_STORIES);
+        assert!(!state.snapshots.contains_key(&42));
+    }
+
+    #[test]
+    fn stories_from_muted_domains_and_subdomains_are_muted() {
+        let mutes = Mutes {
+            users: BTreeSet::from(["troll".to_string()]),
+            domains: BTreeSet::from(["rust-lang.org".to_string()]),
+        };
+        let mut other_story = story(1, 1);
+        other_story.url = Url::parse("https://example.com/").unwrap();
+        assert!(mutes.is_story_muted(&story(1, 1)));
+        assert!(!mutes.is_story_muted(&other_story));
+        assert!(mutes.is_user_muted("troll"));
+        assert!(!mutes.is_user_muted("someone"));
+    }
 }
@@ -0,0 +1,88 @@
+use scraper::{Html, Selector};
+use std::error::Error;
+use std::time::Duration;
+use url::Url;
+
+const THUMBNAIL_WIDTH: u32 = 40;
+const THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the terminal can display images, using the kitty or iTerm graphics protocol.
+/// Other terminals would get a rough approximation made of characters, so nothing is
+/// shown there.
+pub fn is_supported() -> bool {
+    console::Term::stdout().is_term()
+        && (viuer::get_kitty_support() != viuer::KittySupport::None || viuer::is_iterm_supported())
+}
+
+/// Print the image of an article: its OpenGraph image if it has one, its favicon
+/// otherwise.
+pub async fn print_thumbnail(article_url: &Url) -> Result<(), Box<dyn Error>> {
+    let client = reqwest::Client::builder()
+        .timeout(THUMBNAIL_TIMEOUT)
+        .build()?;
+    let html = client
+        .get(article_url.as_str())
+        .send()
+        .await?
+        .text()
+        .await?;
+    let image_url = image_url(&html, article_url);
+    let bytes = client
+        .get(image_url.as_str())
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    let image = image::load_from_memory(&bytes)?;
+    let config = viuer::Config {
+        width: Some(THUMBNAIL_WIDTH),
+        absolute_offset: false,
+        ..Default::default()
+    };
+    viuer::print(&image, &config)?;
+    Ok(())
+}
+
+/// URL of the image representing a page: its `og:image`, then the icon it declares, then
+/// `/favicon.ico`.
+fn image_url(html: &str, page_url: &Url) -> Url {
+    let document = Html::parse_document(html);
+    let selectors = [
+        ("meta[property='og:image']", "content"),
+        ("link[rel~='icon']", "href"),
+    ];
+    selectors
+        .iter()
+        .find_map(|(selector, attr)| {
+            let selector = Selector::parse(selector).unwrap();
+            let value = document.select(&selector).next()?.value().attr(attr)?;
+            page_url.join(value).ok()
+        })
+        .unwrap_or_else(|| page_url.join("/favicon.ico").unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_url_prefers_opengraph_then_icon() {
+        let page_url = Url::parse("https://example.com/blog/post").unwrap();
+        let image_url = |html| image_url(html, &page_url).to_string();
+        assert_eq!(
+            image_url(
+                r#"<head><link rel="icon" href="/icon.png"><meta property="og:image" content="cover.jpg"></head>"#
+            ),
+            "https://example.com/blog/cover.jpg"
+        );
+        assert_eq!(
+            image_url(r#"<head><link rel="shortcut icon" href="/icon.png"></head>"#),
+            "https://example.com/icon.png"
+        );
+        assert_eq!(
+            image_url("<p>No image</p>"),
+            "https://example.com/favicon.ico"
+        );
+    }
+}
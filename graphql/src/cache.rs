@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Values kept in memory for a short time, so that a burst of identical queries doesn’t
+/// scrape HackerNews again for each of them.
+pub struct Cache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Cache<K, V> {
+    /// Create a cache keeping values for `ttl`. Nothing is cached if it is zero.
+    pub fn new(ttl: Duration) -> Self {
+        Cache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Value for a key, if it was inserted less than `ttl` ago.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(inserted, _)| inserted.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone())
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        // Expired values are removed here, so that the cache doesn’t grow indefinitely.
+        entries.retain(|_, (inserted, _)| inserted.elapsed() < self.ttl);
+        entries.insert(key, (Instant::now(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_expire_after_ttl() {
+        let cache = Cache::new(Duration::from_millis(50));
+        cache.insert(1, "one");
+        assert_eq!(cache.get(&1), Some("one"));
+        assert_eq!(cache.get(&2), None);
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn nothing_is_cached_with_a_zero_ttl() {
+        let cache = Cache::new(Duration::ZERO);
+        cache.insert(1, "one");
+        assert_eq!(cache.get(&1), None);
+    }
+}
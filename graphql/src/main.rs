@@ -1,56 +1,128 @@
 #[macro_use]
 extern crate juniper;
 
-use std::rc::Rc;
-
-use juniper::{EmptySubscription, FieldError, GraphQLObject, RootNode};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::{IpAddr, Ipv4Addr},
+    pin::Pin,
+    rc::Rc,
+    sync::Arc,
+    time::Duration,
+};
+
+use cache::Cache;
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
+use juniper::{FieldError, GraphQLObject, RootNode};
+use juniper_graphql_ws::ConnectionConfig;
+use juniper_warp::subscriptions::serve_graphql_ws;
+use poller::Pollers;
+use single_flight::SingleFlight;
+use tokio::sync::{broadcast::error::RecvError, OwnedSemaphorePermit, Semaphore};
 use warp::{hyper::Uri, Filter};
 
-#[derive(GraphQLObject)]
+mod cache;
+mod poller;
+mod single_flight;
+
+#[derive(Clone)]
 /// Information about a story.
-struct Story {
+struct Story(hnapi::Story);
+
+impl Story {
+    pub fn from_api_story(story: &hnapi::Story) -> Story {
+        Story(story.clone())
+    }
+}
+
+/// Information about a story.
+#[graphql_object(context = Context, name = "Story")]
+impl Story {
     /// ID of the story.
-    pub id: i32,
+    fn id(&self) -> i32 {
+        self.0.id as i32
+    }
+
     /// Story title.
-    pub title: String,
+    fn title(&self) -> &str {
+        &self.0.title
+    }
+
     /// Story full URL. For the text stories, the URL will be on “news.ycombinator.org”.
-    pub url: String,
+    fn url(&self) -> String {
+        self.0.url.to_string()
+    }
+
     /// URL as it is display. Often the domain only (e.g. “google.com”), possibly with
     /// additions (e.g. “github.com/scastiel”).
-    pub url_displayed: Option<String>,
+    fn url_displayed(&self) -> Option<&str> {
+        self.0.url_displayed.as_deref()
+    }
+
     /// Parameter to give to `upvote` mutation to be able to upvote a story. Will be null if
     /// not logged in.
-    pub upvote_auth: Option<String>,
+    fn upvote_auth(&self) -> Option<&str> {
+        self.0.upvote_auth.as_deref()
+    }
+
     /// Nickname of the user who posted the story.
-    pub user: Option<String>,
+    fn user(&self) -> Option<&str> {
+        self.0.user.as_deref()
+    }
+
     /// Score of the story at this instant.
-    pub score: Option<i32>,
+    fn score(&self) -> Option<i32> {
+        self.0.score.map(|score| score as i32)
+    }
+
     /// Date the story was posted.
-    pub date: String,
+    fn date(&self) -> String {
+        self.0.date.to_string()
+    }
+
     /// Date the story was posted, as it is displayed, e.g. “2 months ago”.
-    pub date_displayed: String,
+    fn date_displayed(&self) -> &str {
+        &self.0.date_displayed
+    }
+
     /// Number of comments posted on the story.
-    pub comment_count: Option<i32>,
-}
+    fn comment_count(&self) -> Option<i32> {
+        self.0.comment_count.map(|count| count as i32)
+    }
 
-impl Story {
-    pub fn from_api_story(story: &hnapi::Story) -> Story {
-        Story {
-            id: story.id as i32,
-            title: story.title.clone(),
-            url: story.url.to_string(),
-            url_displayed: story.url_displayed.clone(),
-            upvote_auth: story.upvote_auth.clone(),
-            user: story.user.clone(),
-            score: story.score.map(|score| score as i32),
-            date: story.date.to_string(),
-            date_displayed: story.date_displayed.clone(),
-            comment_count: story.comment_count.map(|score| score as i32),
-        }
+    /// URL of the story’s page on HackerNews, where it is discussed (e.g.
+    /// “https://news.ycombinator.com/item?id=27883047”).
+    fn comments_url(&self) -> String {
+        self.0.comments_url()
+    }
+
+    /// Estimated time to read the story in minutes, at 200 words per minute: the time to
+    /// read the article it links to, or its own text for text stories. Null if it can’t be
+    /// estimated, e.g. when the website of the article can’t be reached. It is only
+    /// computed when selected, but costs one more request per story, to the website of
+    /// the article (or to HackerNews for text stories), so selecting it on a whole list
+    /// makes the query much slower.
+    async fn reading_time_minutes(&self, context: &Context) -> Result<Option<i32>, FieldError> {
+        with_timeout(context, async {
+            let _permit = if self.0.is_text_post() {
+                Some(hn_permit(&context.hn_requests).await)
+            } else {
+                None
+            };
+            let minutes = match hnapi::story_reading_time(&self.0).await {
+                Ok(minutes) => minutes,
+                // Only the errors of HackerNews are reported, not the ones of other websites.
+                Err(_) if !self.0.is_text_post() => None,
+                Err(err) => return Err(hn_error(&err)),
+            };
+            Ok(minutes.map(|minutes| minutes as i32))
+        })
+        .await
     }
 }
 
-#[derive(GraphQLObject)]
+#[derive(GraphQLObject, Clone)]
+#[graphql(context = Context)]
 /// Combination of a story and the rank at which it is displayed, depending on
 /// the request returning the story.
 struct StoryWithRank {
@@ -58,6 +130,9 @@ struct StoryWithRank {
     pub rank: i32,
     /// Information about the story.
     pub story: Story,
+    /// Details of the story (HTML content and comments), only fetched when asked with
+    /// `withDetails`.
+    pub details: Option<StoryWithDetails>,
 }
 
 impl StoryWithRank {
@@ -65,6 +140,7 @@ impl StoryWithRank {
         StoryWithRank {
             rank: rank as i32,
             story: Story::from_api_story(story),
+            details: None,
         }
     }
 }
@@ -105,7 +181,7 @@ impl StoryList {
     }
 }
 
-#[derive(GraphQLObject)]
+#[derive(GraphQLObject, Clone)]
 /// Comment posted on a story. A comment can have a parent if it is a reply
 /// to another comment, and can have children.
 struct Comment {
@@ -121,8 +197,15 @@ struct Comment {
     pub date_displayed: String,
     /// HTML content of the comment.
     pub html_content: String,
+    /// Whether the comment is a placeholder for a deleted, flagged or dead comment.
+    pub deleted: bool,
     /// List of the IDs of reply comments.
     pub children: Vec<i32>,
+    /// Depth of the comment in the thread: 0 for a comment posted on the story (or for
+    /// the comment returned by the `comment` query), 1 for a reply to it, etc.
+    pub depth: i32,
+    /// Number of direct replies to the comment (not counting replies to the replies).
+    pub reply_count: i32,
 }
 
 impl Comment {
@@ -134,14 +217,16 @@ impl Comment {
             date: comment.date.to_string(),
             date_displayed: comment.date_displayed.clone(),
             html_content: comment.html_content.clone(),
+            deleted: comment.deleted,
             children: comment
-                .children
-                .borrow()
+                .children()
                 .iter()
                 .map(|child| child.id as i32)
                 .collect(),
+            depth: comment.depth() as i32,
+            reply_count: comment.reply_count() as i32,
         }];
-        let mut children = Comment::flatten_tree(&comment.children.borrow(), Some(comment.id));
+        let mut children = Comment::flatten_tree(&comment.children(), Some(comment.id));
         comments.append(&mut children);
         comments
     }
@@ -154,7 +239,8 @@ impl Comment {
     }
 }
 
-#[derive(GraphQLObject)]
+#[derive(GraphQLObject, Clone)]
+#[graphql(context = Context)]
 /// Combination of a story, its HTML content, and its comments.
 struct StoryWithDetails {
     /// Information about the story.
@@ -163,14 +249,22 @@ struct StoryWithDetails {
     pub html_content: Option<String>,
     /// List of the comments posted on the story.
     pub comments: Vec<Comment>,
+    /// Whether some comments were left out because fetching them all took too long.
+    pub truncated: bool,
+    /// Number of comments actually returned in `comments`, not counting the deleted
+    /// ones. When it is lower than the story’s `commentCount`, some comments are missing.
+    pub parsed_comment_count: i32,
 }
 
 impl StoryWithDetails {
     pub fn from_api_story(details: &hnapi::StoryWithDetails) -> StoryWithDetails {
+        let comments = Comment::flatten_tree(&details.comments, None);
         StoryWithDetails {
             story: Story::from_api_story(&details.story),
             html_content: details.html_content.clone(),
-            comments: Comment::flatten_tree(&details.comments, None),
+            parsed_comment_count: comments.iter().filter(|comment| !comment.deleted).count() as i32,
+            comments,
+            truncated: details.truncated,
         }
     }
 }
@@ -203,12 +297,16 @@ impl User {
 pub struct Auth {
     /// The auth token to put the in `Authorization` header in authenticated requets.
     pub token: String,
+    /// Date the token expires, after which you need to login again. Null if HackerNews
+    /// didn’t tell it.
+    pub expires: Option<String>,
 }
 
 impl Auth {
-    pub fn new(token: &str) -> Auth {
+    pub fn from_api_auth(auth: &hnapi::Auth) -> Auth {
         Auth {
-            token: token.to_string(),
+            token: auth.token(),
+            expires: auth.expires.map(|expires| expires.to_string()),
         }
     }
 }
@@ -219,8 +317,12 @@ struct StoriesInListInput {
     list: Option<StoryList>,
     /// Page number, starting from 1 (default: first page). To be consistent
     /// with what HN’s website, will return the first page if lower than 1,
-    /// and an empty page if greater than what HN accepts.
+    /// and an empty page if greater than what HN accepts. Pages greater than 1000
+    /// are rejected.
     page: Option<i32>,
+    /// Whether to also get the details of each story, as the `story` query does, in
+    /// their `details` field (default: false).
+    with_details: Option<bool>,
 }
 
 #[derive(GraphQLInputObject)]
@@ -239,9 +341,73 @@ struct UpvoteStoryInput {
     upvote_auth: String,
 }
 
-#[derive(Default, Clone)]
+/// Stories lists are identified by list URL, page and auth token (which changes
+/// `upvoteAuth`).
+type StoriesKey = (String, usize, Option<String>);
+type StoriesResult = Result<Vec<StoryWithRank>, Arc<hnapi::HnError>>;
+type StoryResult = Result<Option<StoryWithDetails>, Arc<hnapi::HnError>>;
+
+/// Requests to HackerNews shared between the queries, created once for the server.
+#[derive(Default)]
+struct InFlight {
+    stories: SingleFlight<StoriesKey, StoriesResult>,
+    /// Story details, by story ID.
+    story: SingleFlight<u32, StoryResult>,
+}
+
+/// Responses from HackerNews recently obtained, created once for the server.
+struct Responses {
+    stories: Cache<StoriesKey, Vec<StoryWithRank>>,
+    story: Cache<u32, Option<StoryWithDetails>>,
+}
+
+impl Responses {
+    fn new(ttl: Duration) -> Responses {
+        Responses {
+            stories: Cache::new(ttl),
+            story: Cache::new(ttl),
+        }
+    }
+}
+
+/// Default time responses from HackerNews are kept, which can be changed with the
+/// `CACHE_TTL_SECONDS` environment variable (0 to disable the cache).
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Clone)]
 struct Context {
     pub auth_token: Option<String>,
+    in_flight: Arc<InFlight>,
+    cache: Arc<Responses>,
+    pollers: Arc<Pollers>,
+    /// Time a query can take at most.
+    timeout: Duration,
+    /// Bounds the number of concurrent fetches from HackerNews, shared by all queries.
+    hn_requests: Arc<Semaphore>,
+}
+
+impl juniper::Context for Context {}
+
+/// Default time a query can take before failing with a `TIMEOUT` error, which can be
+/// changed with the `QUERY_TIMEOUT_SECONDS` environment variable.
+const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default interval between two checks of a list for the `newStories` subscription,
+/// which can be changed with the `POLL_INTERVAL_SECONDS` environment variable.
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default maximum number of concurrent fetches from HackerNews, which can be changed
+/// with the `HN_MAX_CONCURRENT_REQUESTS` environment variable.
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// Wait until a fetch from HackerNews can be made. It can be made as long as the
+/// returned permit is not dropped.
+async fn hn_permit(hn_requests: &Arc<Semaphore>) -> OwnedSemaphorePermit {
+    hn_requests
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("The semaphore is never closed")
 }
 
 struct Query;
@@ -253,48 +419,237 @@ impl Query {
         context: &Context,
         input: StoriesInListInput,
     ) -> Result<Vec<StoryWithRank>, FieldError> {
-        let stories = hnapi::stories_list(
-            input.list.unwrap_or_default().to_api_story_list(),
-            input.page.unwrap_or(1) as usize,
-            &context.auth_token,
-        )
-        .await?;
-        let mut ranks: Vec<usize> = stories.keys().copied().collect();
-        ranks.sort_unstable();
-        Ok(ranks
-            .iter()
-            .map(|rank| {
-                let story = stories.get(rank).unwrap();
-                StoryWithRank::from_api_story(*rank, story)
-            })
-            .collect())
+        with_timeout(context, async {
+            let stories =
+                stories_with_rank(context, input.list.unwrap_or_default(), input.page).await?;
+            load_details(context, stories, input.with_details).await
+        })
+        .await
+    }
+
+    /// Get all the stories for a given list at a given page. Same as `stories`, with the
+    /// list and the page (starting from 1, default: first page) as arguments.
+    async fn list(
+        context: &Context,
+        list: StoryList,
+        page: Option<i32>,
+        with_details: Option<bool>,
+    ) -> Result<Vec<StoryWithRank>, FieldError> {
+        with_timeout(context, async {
+            let stories = stories_with_rank(context, list, page).await?;
+            load_details(context, stories, with_details).await
+        })
+        .await
     }
 
     /// Get the details about a given story. Will return `null` for a non-existent story ID.
-    async fn story(_context: &Context, id: i32) -> Result<Option<StoryWithDetails>, FieldError> {
-        let story_with_details = hnapi::story_details(id as u32).await?;
-        Ok(story_with_details.map(|details| StoryWithDetails::from_api_story(&details)))
+    async fn story(context: &Context, id: i32) -> Result<Option<StoryWithDetails>, FieldError> {
+        with_timeout(context, story_with_details(context, id as u32)).await
+    }
+
+    /// Get a comment and its replies, as a flattened list starting with the comment
+    /// itself (its `parent` will be null). Will return `null` for a non-existent
+    /// comment ID, or the ID of a story.
+    async fn comment(context: &Context, id: i32) -> Result<Option<Vec<Comment>>, FieldError> {
+        with_timeout(context, async {
+            let _permit = hn_permit(&context.hn_requests).await;
+            let comment = hnapi::comment_thread(id as u32)
+                .await
+                .map_err(|err| hn_error(&err))?;
+            Ok(comment.map(|comment| Comment::from_api_comment(&comment, None)))
+        })
+        .await
     }
 
     /// Get the details about a given user. Will return `null` for a non-existent user ID.
-    async fn user(_context: &Context, id: String) -> Result<Option<User>, FieldError> {
-        let user = hnapi::user_details(&id).await?;
-        Ok(user.map(|user| User::from_api_user(&user)))
+    async fn user(context: &Context, id: String) -> Result<Option<User>, FieldError> {
+        with_timeout(context, async {
+            let _permit = hn_permit(&context.hn_requests).await;
+            let user = hnapi::user_details(&id)
+                .await
+                .map_err(|err| hn_error(&err))?;
+            Ok(user.map(|user| User::from_api_user(&user)))
+        })
+        .await
     }
 
     /// Login and get the auth token used for next requests.
-    async fn login(_context: &Context, input: AuthInput) -> Result<Auth, FieldError> {
-        if let Some((token, _)) = hnapi::login(&input.username, &input.password).await? {
-            Ok(Auth::new(&token))
+    async fn login(context: &Context, input: AuthInput) -> Result<Auth, FieldError> {
+        let auth = with_timeout(context, async {
+            let _permit = hn_permit(&context.hn_requests).await;
+            hnapi::login(&input.username, &input.password)
+                .await
+                .map_err(|err| hn_error(&err))
+        })
+        .await?;
+        if let Some(auth) = auth {
+            Ok(Auth::from_api_auth(&auth))
         } else {
-            Err(FieldError::new(
-                "Invalid credentials.",
-                graphql_value!(None),
+            Err(error_with_code("Invalid credentials.", UNAUTHENTICATED))
+        }
+    }
+}
+
+/// Result of a query, or a `TIMEOUT` error if it takes too long, e.g. because
+/// HackerNews is slow to answer.
+async fn with_timeout<T>(
+    context: &Context,
+    query: impl Future<Output = Result<T, FieldError>>,
+) -> Result<T, FieldError> {
+    tokio::time::timeout(context.timeout, query)
+        .await
+        .unwrap_or_else(|_| {
+            Err(error_with_code(
+                "HackerNews took too long to answer.",
+                TIMEOUT,
             ))
+        })
+}
+
+/// Stories of a list, from the cache or sharing the request with the identical ones in
+/// flight.
+async fn stories_with_rank(
+    context: &Context,
+    list: StoryList,
+    page: Option<i32>,
+) -> Result<Vec<StoryWithRank>, FieldError> {
+    let list = list.to_api_story_list();
+    let page = validate_page(page)?;
+    let auth_token = context.auth_token.clone();
+    let key = (list.url(), page, auth_token.clone());
+    if let Some(stories) = context.cache.stories.get(&key) {
+        return Ok(stories);
+    }
+    let hn_requests = context.hn_requests.clone();
+    let fetch = move || {
+        async move {
+            let _permit = hn_permit(&hn_requests).await;
+            let stories = hnapi::stories_list(list, page, &auth_token)
+                .await
+                .map_err(Arc::new)?;
+            Ok(stories_by_rank(&stories))
+        }
+        .boxed()
+    };
+    let stories = context
+        .in_flight
+        .stories
+        .run(key.clone(), fetch)
+        .await
+        .map_err(|err| hn_error(&err))?;
+    context.cache.stories.insert(key, stories.clone());
+    Ok(stories)
+}
+
+/// Details of a story, from the cache or sharing the request with the identical ones in
+/// flight.
+async fn story_with_details(
+    context: &Context,
+    id: u32,
+) -> Result<Option<StoryWithDetails>, FieldError> {
+    if let Some(story) = context.cache.story.get(&id) {
+        return Ok(story);
+    }
+    let hn_requests = context.hn_requests.clone();
+    let fetch = move || {
+        async move {
+            let _permit = hn_permit(&hn_requests).await;
+            let story_with_details = hnapi::story_details(id).await.map_err(Arc::new)?;
+            Ok(story_with_details.map(|details| StoryWithDetails::from_api_story(&details)))
         }
+        .boxed()
+    };
+    let story = context
+        .in_flight
+        .story
+        .run(id, fetch)
+        .await
+        .map_err(|err| hn_error(&err))?;
+    context.cache.story.insert(id, story.clone());
+    Ok(story)
+}
+
+/// Maximum number of story details fetched at the same time for a single query, so
+/// that a list asked with its details doesn’t take all the requests to HackerNews.
+const MAX_CONCURRENT_DETAILS: usize = 4;
+
+/// Stories with their details, if asked with `withDetails`.
+async fn load_details(
+    context: &Context,
+    stories: Vec<StoryWithRank>,
+    with_details: Option<bool>,
+) -> Result<Vec<StoryWithRank>, FieldError> {
+    if !with_details.unwrap_or(false) {
+        return Ok(stories);
+    }
+    futures::stream::iter(stories)
+        .map(|mut story| async move {
+            story.details = story_with_details(context, story.story.0.id).await?;
+            Ok::<_, FieldError>(story)
+        })
+        .buffered(MAX_CONCURRENT_DETAILS)
+        .try_collect()
+        .await
+}
+
+/// Highest page number accepted, far beyond the last page HN has for any list.
+const MAX_PAGE: i32 = 1000;
+
+/// Page number to request, from the one given to a query. As on HN’s website, pages
+/// lower than 1 are the first one.
+fn validate_page(page: Option<i32>) -> Result<usize, FieldError> {
+    match page.unwrap_or(1) {
+        page if page > MAX_PAGE => Err(error_with_code(
+            &format!("Invalid page number, it must be at most {}.", MAX_PAGE),
+            BAD_USER_INPUT,
+        )),
+        page => Ok(page.max(1) as usize),
     }
 }
 
+fn stories_by_rank(stories: &HashMap<usize, hnapi::Story>) -> Vec<StoryWithRank> {
+    let mut ranks: Vec<usize> = stories.keys().copied().collect();
+    ranks.sort_unstable();
+    ranks
+        .iter()
+        .map(|rank| {
+            let story = stories.get(rank).unwrap();
+            StoryWithRank::from_api_story(*rank, story)
+        })
+        .collect()
+}
+
+/// Codes of the errors, in their `extensions`, for clients to handle them.
+const NOT_FOUND: &str = "NOT_FOUND";
+const UNAUTHENTICATED: &str = "UNAUTHENTICATED";
+const UPSTREAM_UNAVAILABLE: &str = "UPSTREAM_UNAVAILABLE";
+const UPSTREAM_ERROR: &str = "UPSTREAM_ERROR";
+const BAD_USER_INPUT: &str = "BAD_USER_INPUT";
+const TIMEOUT: &str = "TIMEOUT";
+const RATE_LIMITED: &str = "RATE_LIMITED";
+
+fn error_with_code(message: &str, code: &'static str) -> FieldError {
+    FieldError::new(message, graphql_value!({ "code": code }))
+}
+
+/// GraphQL error for an error from hnapi, with a code telling whether HackerNews
+/// couldn’t be reached, or answered with an error.
+fn hn_error(err: &hnapi::HnError) -> FieldError {
+    let code = match err {
+        hnapi::HnError::Unauthenticated => UNAUTHENTICATED,
+        hnapi::HnError::Rejected(_) => BAD_USER_INPUT,
+        hnapi::HnError::RateLimited => RATE_LIMITED,
+        hnapi::HnError::Status(404) => NOT_FOUND,
+        hnapi::HnError::Status(500..=599) => UPSTREAM_UNAVAILABLE,
+        hnapi::HnError::Status(_) => UPSTREAM_ERROR,
+        hnapi::HnError::Request(err) if err.is_timeout() || err.is_connect() => {
+            UPSTREAM_UNAVAILABLE
+        }
+        hnapi::HnError::Request(_) => UPSTREAM_ERROR,
+    };
+    error_with_code(&err.to_string(), code)
+}
+
 struct Mutation;
 
 #[graphql_object(context = Context)]
@@ -302,60 +657,225 @@ impl Mutation {
     /// Upvote a story. You must be authenticated.
     async fn upvote_story(context: &Context, input: UpvoteStoryInput) -> Result<bool, FieldError> {
         if let Some(auth_token) = context.auth_token.as_ref() {
-            if let Ok(ok) =
-                hnapi::upvote_story(input.id as u32, &input.upvote_auth, auth_token).await
-            {
-                if ok {
-                    Ok(true)
-                } else {
-                    Err(FieldError::new(
-                        "Authentication error. You may need to login again.",
-                        graphql_value!(None),
-                    ))
-                }
+            let ok = with_timeout(context, async {
+                let _permit = hn_permit(&context.hn_requests).await;
+                hnapi::upvote_story(input.id as u32, &input.upvote_auth, auth_token)
+                    .await
+                    .map_err(|err| hn_error(&err))
+            })
+            .await?;
+            if ok {
+                Ok(true)
             } else {
-                Err(FieldError::new(
-                    "An error occurred while upvoting the story.",
-                    graphql_value!(None),
+                Err(error_with_code(
+                    "Authentication error. You may need to login again.",
+                    UNAUTHENTICATED,
                 ))
             }
         } else {
-            Err(FieldError::new(
+            Err(error_with_code(
                 "You must be logged in to upvote a story. No auth token found in the headers.",
-                graphql_value!(None),
+                UNAUTHENTICATED,
             ))
         }
     }
 }
 
-type Schema = RootNode<'static, Query, Mutation, EmptySubscription<Context>>;
+type StoryStream = Pin<Box<dyn Stream<Item = Result<StoryWithRank, FieldError>> + Send>>;
+
+struct Subscription;
+
+#[graphql_subscription(context = Context)]
+impl Subscription {
+    /// Stories appearing on the first page of a list (default: top stories). The list is
+    /// checked regularly, and only the stories that weren’t there at the previous
+    /// check are sent.
+    async fn new_stories(context: &Context, list: Option<StoryList>) -> StoryStream {
+        let receiver = context
+            .pollers
+            .subscribe(list.unwrap_or_default().to_api_story_list());
+        let stories = futures::stream::unfold(receiver, |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(story) => return Some((Ok(story), receiver)),
+                    // Stories missed by a slow subscriber are skipped.
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Box::pin(stories)
+    }
+}
+
+type Schema = RootNode<'static, Query, Mutation, Subscription>;
+
+fn schema() -> Schema {
+    Schema::new(Query, Mutation, Subscription)
+}
 
 #[tokio::main]
 async fn main() {
-    let schema = Schema::new(Query, Mutation, EmptySubscription::<Context>::new());
-
-    let state = warp::any().and(
-        warp::header("authorization")
-            .map(|auth_token| Context {
-                auth_token: Some(auth_token),
-            })
-            .or(warp::any().map(|| Context { auth_token: None }))
-            .unify(),
-    );
-    let graphql_filter = juniper_warp::make_graphql_filter(schema, state.boxed());
+    let max_concurrent_requests = std::env::var("HN_MAX_CONCURRENT_REQUESTS")
+        .map(|max| {
+            max.parse()
+                .expect("HN_MAX_CONCURRENT_REQUESTS must be a number")
+        })
+        .unwrap_or(DEFAULT_MAX_CONCURRENT_REQUESTS);
+    let cache_ttl = std::env::var("CACHE_TTL_SECONDS")
+        .map(|ttl| Duration::from_secs(ttl.parse().expect("CACHE_TTL_SECONDS must be a number")))
+        .unwrap_or(DEFAULT_CACHE_TTL);
+    let in_flight = Arc::new(InFlight::default());
+    let cache = Arc::new(Responses::new(cache_ttl));
+    let hn_requests = Arc::new(Semaphore::new(max_concurrent_requests));
+    let poll_interval = std::env::var("POLL_INTERVAL_SECONDS")
+        .map(|interval| {
+            Duration::from_secs(
+                interval
+                    .parse()
+                    .expect("POLL_INTERVAL_SECONDS must be a number"),
+            )
+        })
+        .unwrap_or(DEFAULT_POLL_INTERVAL);
+    let pollers = Arc::new(Pollers::new(poll_interval, hn_requests.clone()));
+    let timeout = std::env::var("QUERY_TIMEOUT_SECONDS")
+        .map(|timeout| {
+            Duration::from_secs(
+                timeout
+                    .parse()
+                    .expect("QUERY_TIMEOUT_SECONDS must be a number"),
+            )
+        })
+        .unwrap_or(DEFAULT_QUERY_TIMEOUT);
+    let state = warp::any()
+        .and(
+            warp::header::optional("authorization").map(move |auth_token| Context {
+                auth_token,
+                in_flight: in_flight.clone(),
+                cache: cache.clone(),
+                pollers: pollers.clone(),
+                timeout,
+                hn_requests: hn_requests.clone(),
+            }),
+        )
+        .boxed();
+    let graphql_filter = juniper_warp::make_graphql_filter(schema(), state.clone());
 
     let port = std::env::var("PORT")
         .map(|p| p.parse().expect("PORT must be a number"))
         .unwrap_or(8080);
-    println!("Listening on port {}...", port);
-
-    let playground_route = warp::get()
-        .and(warp::path("playground"))
-        .and(juniper_warp::playground_filter("/graphql", None));
+    // Listening on all interfaces by default, e.g. for Heroku. Use HOST=127.0.0.1 to only
+    // accept local connections.
+    let host: IpAddr = std::env::var("HOST")
+        .map(|h| {
+            h.parse()
+                .expect("HOST must be an IP address, e.g. 127.0.0.1")
+        })
+        .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+    println!("Listening on {}:{}...", host, port);
+
+    let playground_route =
+        warp::get()
+            .and(warp::path("playground"))
+            .and(juniper_warp::playground_filter(
+                "/graphql",
+                Some("/subscriptions"),
+            ));
     let graphql_route = warp::path("graphql").and(graphql_filter);
+    let root_node = Arc::new(schema());
+    let subscriptions_route = warp::path("subscriptions")
+        .and(warp::ws())
+        .and(state)
+        .map(move |ws: warp::ws::Ws, context: Context| {
+            let root_node = root_node.clone();
+            ws.on_upgrade(move |websocket| async move {
+                let result =
+                    serve_graphql_ws(websocket, root_node, ConnectionConfig::new(context)).await;
+                if let Err(err) = result {
+                    eprintln!("Websocket error: {}", err);
+                }
+            })
+        })
+        .map(|reply| warp::reply::with_header(reply, "Sec-WebSocket-Protocol", "graphql-ws"));
     let default_route = warp::path::end().map(|| warp::redirect(Uri::from_static("/playground")));
 
-    warp::serve(playground_route.or(graphql_route).or(default_route))
-        .run(([0, 0, 0, 0], port))
-        .await
+    warp::serve(
+        playground_route
+            .or(graphql_route)
+            .or(subscriptions_route)
+            .or(default_route),
+    )
+    .run((host, port))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_lower_than_1_are_the_first_one() {
+        assert_eq!(validate_page(Some(-1)).unwrap(), 1);
+        assert_eq!(validate_page(Some(0)).unwrap(), 1);
+        assert_eq!(validate_page(None).unwrap(), 1);
+        assert_eq!(validate_page(Some(3)).unwrap(), 3);
+    }
+
+    #[test]
+    fn errors_have_a_code() {
+        let error = hn_error(&hnapi::HnError::Unauthenticated);
+        assert_eq!(error.message(), "Invalid or expired auth token");
+        assert_eq!(
+            error.extensions(),
+            &graphql_value!({ "code": "UNAUTHENTICATED" })
+        );
+    }
+
+    #[test]
+    fn error_statuses_have_a_code() {
+        let code = |status| {
+            hn_error(&hnapi::HnError::Status(status))
+                .extensions()
+                .clone()
+        };
+        assert_eq!(code(404), graphql_value!({ "code": "NOT_FOUND" }));
+        assert_eq!(
+            code(502),
+            graphql_value!({ "code": "UPSTREAM_UNAVAILABLE" })
+        );
+        assert_eq!(
+            code(503),
+            graphql_value!({ "code": "UPSTREAM_UNAVAILABLE" })
+        );
+        assert_eq!(code(403), graphql_value!({ "code": "UPSTREAM_ERROR" }));
+    }
+
+    #[tokio::test]
+    async fn slow_queries_time_out() {
+        let hn_requests = Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_REQUESTS));
+        let context = Context {
+            auth_token: None,
+            in_flight: Arc::new(InFlight::default()),
+            cache: Arc::new(Responses::new(Duration::ZERO)),
+            pollers: Arc::new(Pollers::new(DEFAULT_POLL_INTERVAL, hn_requests.clone())),
+            timeout: Duration::from_millis(10),
+            hn_requests,
+        };
+        let result = with_timeout(&context, async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(())
+        })
+        .await;
+        assert_eq!(
+            result.unwrap_err().extensions(),
+            &graphql_value!({ "code": "TIMEOUT" })
+        );
+    }
+
+    #[test]
+    fn absurd_pages_are_rejected() {
+        assert!(validate_page(Some(MAX_PAGE)).is_ok());
+        assert!(validate_page(Some(MAX_PAGE + 1)).is_err());
+        assert!(validate_page(Some(i32::MAX)).is_err());
+    }
 }
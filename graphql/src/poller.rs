@@ -0,0 +1,103 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, Semaphore};
+
+use crate::{hn_permit, stories_by_rank, StoryWithRank};
+
+/// Number of stories kept for subscribers slower to receive them than others.
+const CHANNEL_CAPACITY: usize = 100;
+/// Number of story IDs remembered for each list to tell the new stories, the most recent
+/// ones. Far more than a page, so that a story is only forgotten long after it left it.
+const MAX_KNOWN_STORIES: usize = 1000;
+
+/// Pollers of the story lists, for the `newStories` subscription. Each list is polled
+/// by a single task, started with its first subscriber, and its new stories are sent
+/// to all the subscribers.
+pub struct Pollers {
+    interval: Duration,
+    hn_requests: Arc<Semaphore>,
+    senders: Mutex<HashMap<String, broadcast::Sender<StoryWithRank>>>,
+}
+
+impl Pollers {
+    pub fn new(interval: Duration, hn_requests: Arc<Semaphore>) -> Pollers {
+        Pollers {
+            interval,
+            hn_requests,
+            senders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receive the stories appearing on the first page of a list from now on.
+    pub fn subscribe(&self, list: hnapi::StoryList) -> broadcast::Receiver<StoryWithRank> {
+        let mut senders = self.senders.lock().unwrap();
+        if let Some(sender) = senders.get(&list.url()) {
+            return sender.subscribe();
+        }
+        let (sender, receiver) = broadcast::channel(CHANNEL_CAPACITY);
+        senders.insert(list.url(), sender.clone());
+        tokio::spawn(poll(list, sender, self.interval, self.hn_requests.clone()));
+        receiver
+    }
+}
+
+/// Send the new stories of a list regularly. The stories there on the first check are
+/// only remembered, and the list is not requested while there are no subscribers.
+async fn poll(
+    list: hnapi::StoryList,
+    sender: broadcast::Sender<StoryWithRank>,
+    interval: Duration,
+    hn_requests: Arc<Semaphore>,
+) {
+    let mut known_ids = HashSet::new();
+    let mut first_check = true;
+    loop {
+        if sender.receiver_count() > 0 {
+            let _permit = hn_permit(&hn_requests).await;
+            match hnapi::stories_since(list, &known_ids, &None).await {
+                Ok(stories) => {
+                    remember_stories(&mut known_ids, stories.values().map(|story| story.id));
+                    if !first_check {
+                        for story in stories_by_rank(&stories) {
+                            // Fails only if all the subscribers are gone meanwhile.
+                            let _ = sender.send(story);
+                        }
+                    }
+                    first_check = false;
+                }
+                Err(err) => eprintln!("Unable to check the new stories: {}", err),
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Add the stories to the known ones, keeping only the most recent when there are too
+/// many of them (story IDs are increasing), so that a long-running poller doesn't grow
+/// forever.
+fn remember_stories(known_ids: &mut HashSet<u32>, ids: impl Iterator<Item = u32>) {
+    known_ids.extend(ids);
+    if known_ids.len() > MAX_KNOWN_STORIES {
+        let mut ids: Vec<u32> = known_ids.drain().collect();
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        known_ids.extend(ids.into_iter().take(MAX_KNOWN_STORIES));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_most_recent_stories_are_remembered() {
+        let mut known_ids = HashSet::new();
+        remember_stories(&mut known_ids, 1..=MAX_KNOWN_STORIES as u32);
+        remember_stories(&mut known_ids, [5000, 5001].into_iter());
+        assert_eq!(known_ids.len(), MAX_KNOWN_STORIES);
+        assert!(known_ids.contains(&5001));
+        assert!(!known_ids.contains(&1) && !known_ids.contains(&2));
+        assert!(known_ids.contains(&3));
+    }
+}
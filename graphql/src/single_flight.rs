@@ -0,0 +1,77 @@
+use futures::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+
+/// Requests to HackerNews being made, so that identical concurrent requests share a
+/// single one instead of each scraping the website.
+pub struct SingleFlight<K, V: Clone> {
+    in_flight: Mutex<HashMap<K, Shared<BoxFuture<'static, V>>>>,
+}
+
+impl<K, V: Clone> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        SingleFlight {
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    /// Get the result of the request in flight for a key, or start it with `fetch` if
+    /// there is none.
+    pub async fn run<F>(&self, key: K, fetch: F) -> V
+    where
+        F: FnOnce() -> BoxFuture<'static, V>,
+    {
+        let future = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| fetch().shared())
+            .clone();
+        let value = future.await;
+        // The request is forgotten by the first one to see it completed, even if it
+        // is not the one who started it (it may have been cancelled).
+        let mut in_flight = self.in_flight.lock().unwrap();
+        if in_flight
+            .get(&key)
+            .is_some_and(|future| future.peek().is_some())
+        {
+            in_flight.remove(&key);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn concurrent_requests_are_made_once() {
+        let single_flight: SingleFlight<u32, u32> = SingleFlight::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fetch = || {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                42
+            }
+            .boxed()
+        };
+
+        let (a, b) = tokio::join!(single_flight.run(1, fetch), single_flight.run(1, fetch));
+        assert_eq!((a, b), (42, 42));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Once completed, the next request is made again.
+        single_flight.run(1, fetch).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}